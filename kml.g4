@@ -5,25 +5,157 @@ COMMENT       : '#' ~[\r\n]* -> skip ;
 
 IDENTIFIER    : [a-zA-Z_][a-zA-Z_0-9]* ;
 
+NUMBER        : [0-9]+ ;
+
 WS            : [ \t\r\n]+ -> skip ;
 
 // Parser rules
-kernelModel   : (eventDef | COMMENT)* kernelDef EOF ;
+kernelModel   : (eventDef | COMMENT)* (invariantDef | COMMENT)* (policyDef | COMMENT)* initialDef? kernelDef EOF ;
 
-eventDef      : 'event' IDENTIFIER '{' eventBody '}' ;
+eventDef      : 'interrupt'? 'event' name '{' eventBody '}' ;
+invariantDef  : 'invariant' name ('at' actionKeyword)? '{' expr '}' ;
+initialDef    : 'initial' '{' initialBody '}' ;
 kernelDef     : 'kernel' '{' kernelBody '}' ;
 
 eventBody     : (eventAction | COMMENT)* ;
-kernelBody    : (schedulerDef | eventsDef | COMMENT)* ;
+initialBody   : ((tasksDef | currentDef | initDef | timeDef | varDef) ';'? | COMMENT)* ;
+kernelBody    : (schedulerDef | eventsDef | cpusDef | framesDef | limitDef | onEmptyDef | timeSliceDef
+              | seedDef | COMMENT)* ;
 
+// Blocking actions (block, sleep, wait, sem_down, mutex_lock, read, write, send, call, recv,
+// job_done) leave the cpu idle, the event picks the next task with a following 'sched'
 eventAction   : 'shutdown' 
-              | 'newtask' 
+              | 'newtask' ('(' integer ')')?
               | 'exit' 
               | 'sched' 
-              | 'stop' ;
+              | 'stop'
+              | 'block' '(' name ')'
+              | 'wakeup_one' '(' name ')'
+              | 'wakeup_all' '(' name ')'
+              | 'fork'
+              | 'wait'
+              | 'setprio' '(' integer ')'
+              | 'setnice' '(' integer ')'
+              | 'settickets' '(' NUMBER ')'
+              | 'sem_create' '(' name ',' NUMBER ')'
+              | 'sem_down' '(' name ')'
+              | 'sem_up' '(' name ')'
+              | 'mutex_lock' '(' name ')'
+              | 'mutex_unlock' '(' name ')'
+              | 'tick'
+              | 'sleep' '(' NUMBER ')'
+              | 'mmap' '(' NUMBER ',' NUMBER ',' IDENTIFIER ')'
+              | 'munmap' '(' NUMBER ',' NUMBER ')'
+              | 'page_fault' '(' NUMBER ',' IDENTIFIER ')'
+              | 'brk' '(' NUMBER ')'
+              | 'open' '(' name ')'
+              | 'close' '(' NUMBER ')'
+              | 'dup' '(' NUMBER ')'
+              | 'pipe' ('(' NUMBER ')')?
+              | 'read' '(' NUMBER ',' NUMBER ')'
+              | 'write' '(' NUMBER ',' NUMBER ')'
+              | 'kill' '(' NUMBER ',' NUMBER ')'
+              | 'sigmask' '(' numberList ')'
+              | 'sigaction' '(' NUMBER ',' ('default' | 'ignore' | 'handle') ')'
+              | 'send' '(' name ',' NUMBER ')'
+              | 'recv' '(' name ')'
+              | 'call' '(' name ',' NUMBER ')'
+              | 'reply' '(' NUMBER ')'
+              | 'irq_off'
+              | 'irq_on'
+              | 'irq_enter'
+              | 'irq_exit'
+              | 'thread_create'
+              | 'thread_exit'
+              | 'exit_group'
+              | 'preempt'
+              // Period, worst-case execution time and relative deadline
+              | ('periodic' | 'sporadic') '(' NUMBER ',' NUMBER (',' NUMBER)? ')'
+              | 'job_done'
+              | 'release' '(' NUMBER ')' ;
+
+// Keyword of any action above
+actionKeyword : 'shutdown' | 'newtask' | 'exit' | 'sched' | 'stop' | 'block' | 'wakeup_one'
+              | 'wakeup_all' | 'fork' | 'wait' | 'setprio' | 'setnice' | 'settickets'
+              | 'sem_create' | 'sem_down' | 'sem_up' | 'mutex_lock' | 'mutex_unlock' | 'tick' | 'sleep'
+              | 'mmap' | 'munmap' | 'page_fault' | 'brk' | 'open' | 'close' | 'dup' | 'pipe'
+              | 'read' | 'write' | 'kill' | 'sigmask' | 'sigaction' | 'send' | 'recv'
+              | 'call' | 'reply' | 'irq_off' | 'irq_on' | 'irq_enter' | 'irq_exit'
+              | 'thread_create' | 'thread_exit' | 'exit_group' | 'preempt' | 'periodic'
+              | 'sporadic' | 'job_done' | 'release' ;
+
+// Booleans are 0 and 1, comparisons do not chain
+expr          : andExpr ('||' andExpr)* ;
+andExpr       : comparison ('&&' comparison)* ;
+comparison    : unary (('==' | '!=' | '<' | '<=' | '>' | '>=') unary)? ;
+unary         : '!' unary
+              | '(' expr ')'
+              | NUMBER
+              | variable ;
+variable      : 'irq_enabled' | 'irq_depth' | 'time' | 'tasks' | 'ready' | 'blocked' | 'idle'
+              | 'context_switches' | 'max_ready_time' | 'min_scheduled' | 'max_scheduled'
+              | 'deadline_misses' | 'wcet_overruns' ;
+
+tasksDef      : 'tasks' '=' numberList ;
+currentDef    : 'current' '=' (NUMBER | numberList) ;
+initDef       : 'init' '=' NUMBER ;
+timeDef       : 'time' '=' NUMBER ;
+// Initial value of a state variable, a blocked task must not be current
+varDef        : ('priority' | 'nice') '(' NUMBER ')' '=' integer
+              | 'blocked' '(' name ')' '=' numberList
+              | 'sem' '(' name ')' '=' NUMBER
+              | 'mutex' '(' name ')' '=' NUMBER ;
+
+// Scheduler declared in kml, tasks tied on every key are all legal choices. With
+// `preempt` a running task gives way to a ready task that comes strictly before it.
+policyDef     : 'scheduler' name '{' 'pick' sortKey ('then' sortKey)* ('among' 'ready')?
+                'preempt'? '}' ;
+sortKey       : ('min' | 'max') '(' taskField ')' ;
+taskField     : 'id' | 'priority' | 'nice' | 'tickets' | 'deadline' | 'period' | 'queue'
+              | 'ready_time' | 'scheduled' ;
 
 schedulerDef  : 'scheduler' '=' schedulerType ;
-eventsDef     : 'events' '=' '[' (IDENTIFIER (',' IDENTIFIER)*)? ']' ;
+eventsDef     : 'events' '=' '[' (name (',' name)*)? ']' ;
+cpusDef       : 'cpus' '=' NUMBER ;
+framesDef     : 'frames' '=' NUMBER ;
+// A limit `min..max` permits both success and failure between min and max units
+limitDef      : ('max_tasks' | 'max_fds' | 'max_frames') '=' NUMBER ('..' NUMBER)? ;
+onEmptyDef    : 'on_empty' '=' ('idle' | 'shutdown' '(' integer ')' | 'error') ;
+timeSliceDef  : 'time_slice' '=' NUMBER ;
+seedDef       : 'seed' '=' NUMBER ;
+
+schedulerType : 'fifo'
+              | 'random'
+              | 'rr'
+              | 'cfs'
+              | 'edf'
+              | 'rm'
+              | 'lottery'
+              | 'stride'
+              // A scheduler declared in kml or registered by name
+              | name
+              | 'priority' ('(' ('fifo' | 'lowest_id') ')')?
+              // Quantum of each level, then the period of priority boosts
+              | 'mlfq' '(' numberList (',' NUMBER)? ')' ;
 
-schedulerType : 'fifo' 
-              | 'random';
+// Only 'event', 'kernel', 'events', 'scheduler', 'fifo', 'shutdown', 'newtask', 'exit', 'sched'
+// and 'stop' are reserved. Every other word is a keyword only where a rule expects it, elsewhere
+// it is a name.
+name          : IDENTIFIER | contextualWord ;
+contextualWord : 'interrupt' | 'invariant' | 'at' | 'initial' | 'block' | 'wakeup_one'
+              | 'wakeup_all' | 'fork' | 'wait' | 'setprio' | 'setnice' | 'settickets'
+              | 'sem_create' | 'sem_down' | 'sem_up' | 'mutex_lock' | 'mutex_unlock' | 'tick'
+              | 'sleep' | 'mmap' | 'munmap' | 'page_fault' | 'brk' | 'open' | 'close' | 'dup'
+              | 'pipe' | 'read' | 'write' | 'kill' | 'sigmask' | 'sigaction' | 'default'
+              | 'ignore' | 'handle' | 'send' | 'recv' | 'call' | 'reply' | 'irq_off' | 'irq_on'
+              | 'irq_enter' | 'irq_exit' | 'thread_create' | 'thread_exit' | 'exit_group'
+              | 'preempt' | 'periodic' | 'sporadic' | 'job_done' | 'release' | 'irq_enabled'
+              | 'irq_depth' | 'time' | 'tasks' | 'ready' | 'blocked' | 'idle' | 'context_switches'
+              | 'max_ready_time' | 'min_scheduled' | 'max_scheduled' | 'deadline_misses'
+              | 'wcet_overruns' | 'current' | 'init' | 'priority' | 'nice' | 'sem' | 'mutex'
+              | 'pick' | 'then' | 'among' | 'min' | 'max' | 'id' | 'tickets' | 'deadline'
+              | 'period' | 'queue' | 'ready_time' | 'scheduled' | 'cpus' | 'frames' | 'max_tasks'
+              | 'max_fds' | 'max_frames' | 'on_empty' | 'error' | 'time_slice' | 'seed' | 'random'
+              | 'rr' | 'cfs' | 'edf' | 'rm' | 'lottery' | 'stride' | 'lowest_id' | 'mlfq' ;
+integer       : '-'? NUMBER ;
+numberList    : '[' (NUMBER (',' NUMBER)*)? ']' ;
\ No newline at end of file
@@ -0,0 +1,121 @@
+use crate::kernel::{split_event, Kernel};
+use crate::state::AbstractState;
+use serde::Deserialize;
+use std::collections::HashSet;
+
+/// One step of a real kernel execution, the event it handled and the abstract state
+/// it reached afterwards
+#[derive(Debug, Deserialize)]
+pub struct Step {
+    pub event: String,
+    pub state: AbstractState,
+}
+
+/// Check a real kernel execution against the model. After every step the observed
+/// state must be one of the states the model permits, following every model state
+/// that matched so far. Observed states are completed like initial states, and
+/// accounting such as context switches is not compared. Returns the index of the
+/// first step that does not conform.
+pub fn check(kernel: &Kernel, trace: &[Step]) -> Result<(), usize> {
+    let mut models = vec![kernel.clone()];
+    for (i, step) in trace.iter().enumerate() {
+        let (name, cpu) = split_event(&step.event).map_err(|_| i)?;
+        let mut observed = step.state.without_stats();
+        observed.queue_ready();
+        let mut visited = HashSet::new();
+        models = models
+            .iter()
+            .flat_map(|kernel| kernel.successors(name, cpu))
+            .filter(|kernel| {
+                kernel.state().without_stats() == observed && visited.insert(kernel.key())
+            })
+            .collect();
+        if models.is_empty() {
+            return Err(i);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::lex_and_parse_kml;
+    use crate::scheduler::Registry;
+
+    fn kernel(scheduler: &str) -> Kernel {
+        let kml = format!(
+            "event timer {{ tick }}
+            event yield {{ stop sched }}
+            initial {{ tasks = [0, 1, 2]; current = 0; }}
+            kernel {{ scheduler = {} events = [timer, yield] }}",
+            scheduler
+        );
+        Kernel::from_model(lex_and_parse_kml(&kml).unwrap(), None, &Registry::default()).unwrap()
+    }
+
+    /// Steps a real kernel could have taken, as the model takes them
+    fn steps(kernel: &Kernel, events: &[&str]) -> Vec<Step> {
+        let mut kernel = kernel.clone();
+        events
+            .iter()
+            .map(|&event| {
+                kernel.execute(event).unwrap();
+                Step {
+                    event: event.to_string(),
+                    state: kernel.state().clone(),
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn conform_test() {
+        let kernel = kernel("rr");
+        let mut trace = steps(&kernel, &["timer", "yield", "timer", "yield"]);
+        assert_eq!(check(&kernel, &trace), Ok(()));
+        // Observed states may leave out a ready queue in task order, and the accounting
+        // is not compared
+        trace[0].state.ready_queue.clear();
+        for step in trace.iter_mut() {
+            step.state.context_switches = 0;
+        }
+        assert_eq!(check(&kernel, &trace), Ok(()));
+    }
+    #[test]
+    fn diverge_test() {
+        let kernel = kernel("rr");
+        let mut trace = steps(&kernel, &["timer", "yield", "timer"]);
+        trace[1].state.time = 5;
+        assert_eq!(check(&kernel, &trace), Err(1));
+        let mut trace = steps(&kernel, &["timer", "yield"]);
+        trace.push(Step {
+            event: "missing".to_string(),
+            state: kernel.state().clone(),
+        });
+        assert_eq!(check(&kernel, &trace), Err(2));
+    }
+    #[test]
+    fn nondeterministic_test() {
+        // Any ready task may be picked, and each pick is followed until it diverges
+        let kernel = kernel("random");
+        let picks = kernel.successors("yield", 0);
+        assert_eq!(picks.len(), 3);
+        for pick in &picks {
+            let mut trace = vec![Step {
+                event: "yield".to_string(),
+                state: pick.state().clone(),
+            }];
+            trace.extend(steps(pick, &["timer", "yield"]));
+            assert_eq!(check(&kernel, &trace), Ok(()));
+        }
+        // No pick leaves the cpu idle
+        let mut state = picks[0].state().clone();
+        state.cpus[0].current = None;
+        let trace = [Step {
+            event: "yield".to_string(),
+            state,
+        }];
+        assert_eq!(check(&kernel, &trace), Err(0));
+    }
+}
@@ -1,9 +1,25 @@
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug, PartialEq)]
 pub enum Error {
     LexError,
     SyntaxError,
     BadKernelConfig,
+    BadInitialState,
     TaskNotFound,
+    TaskNotRunning,
+    CpuNotFound,
+    CpuIdle,
     EventNotFound,
     NoReadyTask,
-}
\ No newline at end of file
+    BadSchedulerPick,
+    BadArgument,
+    ObjectNotFound,
+    ObjectExists,
+    NotOwner,
+    Deadlock,
+    MemoryNotModeled,
+    BadAddress,
+    BadDescriptor,
+    BrokenPipe,
+    InvariantViolated,
+}
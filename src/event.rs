@@ -1,8 +1,9 @@
 use crate::parse::{EventAction, EventDef};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Event {
     name: String,
+    interrupt: bool,
     actions: Vec<EventAction>,
 }
 
@@ -10,12 +11,16 @@ impl Event {
     pub fn new(def: EventDef) -> Self {
         Self {
             name: def.name.0,
+            interrupt: def.interrupt,
             actions: def.body,
         }
     }
     pub fn name(&self) -> &str {
         &self.name
     }
+    pub fn interrupt(&self) -> bool {
+        self.interrupt
+    }
     pub fn actions(&self) -> &[EventAction] {
         &self.actions
     }
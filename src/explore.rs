@@ -0,0 +1,159 @@
+use crate::error::Error;
+use crate::kernel::Kernel;
+use crate::state::CpuId;
+use std::collections::HashSet;
+
+/// What exploration found at one depth
+#[derive(Debug, Default, PartialEq)]
+pub struct Depth {
+    /// Distinct states first reached
+    pub states: usize,
+    /// Those of them with a job past its deadline
+    pub missed: usize,
+    /// Event paths from the initial state whose last event violates an invariant
+    pub violations: Vec<Vec<String>>,
+}
+
+/// Breadth-first exploration of all kernel states reachable within `depth` events.
+/// Every enabled event may be delivered to every cpu, and every nondeterministic
+/// choice inside an event is followed, so all interleavings are covered.
+/// Returns what was found at each depth, states violating an invariant are not
+/// explored further.
+pub fn explore(kernel: &Kernel, depth: usize) -> Vec<Depth> {
+    let missed = |kernel: &Kernel| {
+        kernel
+            .state()
+            .tasks
+            .iter()
+            .any(|task| task.rt.as_ref().is_some_and(|rt| rt.missed))
+    };
+    let mut visited = HashSet::new();
+    visited.insert(kernel.key());
+    let mut frontier = vec![(kernel.clone(), Vec::new())];
+    let mut depths = vec![Depth {
+        states: 1,
+        missed: missed(kernel) as usize,
+        violations: Vec::new(),
+    }];
+    for _ in 0..depth {
+        let mut next = Vec::new();
+        let mut violations = Vec::new();
+        for (kernel, path) in &frontier {
+            if kernel.shutdown_code().is_some() {
+                continue;
+            }
+            for event in kernel.events() {
+                for cpu in 0..kernel.state().cpus.len() as CpuId {
+                    let mut path = path.clone();
+                    path.push(match cpu {
+                        0 => event.name().to_owned(),
+                        _ => format!("{}@{}", event.name(), cpu),
+                    });
+                    for outcome in kernel.outcomes(event.name(), cpu) {
+                        match outcome {
+                            Ok(successor) => {
+                                if visited.insert(successor.key()) {
+                                    next.push((successor, path.clone()));
+                                }
+                            }
+                            Err(Error::InvariantViolated) => {
+                                if !violations.contains(&path) {
+                                    violations.push(path.clone());
+                                }
+                            }
+                            Err(_) => {}
+                        }
+                    }
+                }
+            }
+        }
+        depths.push(Depth {
+            states: next.len(),
+            missed: next.iter().filter(|(kernel, _)| missed(kernel)).count(),
+            violations,
+        });
+        if next.is_empty() {
+            break;
+        }
+        frontier = next;
+    }
+    depths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::lex_and_parse_kml;
+    use crate::scheduler::{Registry, Scheduler};
+    use crate::state::{AbstractState, TaskId};
+
+    fn kernel(kml: &str) -> Kernel {
+        Kernel::from_model(lex_and_parse_kml(kml).unwrap(), None, &Registry::default()).unwrap()
+    }
+
+    #[test]
+    fn explore_test() {
+        let kernel = kernel(
+            "event spin { stop sched }
+            initial { tasks = [0, 1]; current = 0; }
+            kernel {
+                scheduler = rr
+                events = [spin]
+            }",
+        );
+        // Switching back and forth only grows the accounting, which is not explored
+        let depths = explore(&kernel, 3);
+        let states = depths.iter().map(|depth| depth.states).collect::<Vec<_>>();
+        assert_eq!(states, vec![1, 1, 0]);
+    }
+    #[test]
+    fn explore_policy_test() {
+        /// Alternates between the lowest and the highest ready task
+        #[derive(Debug, Clone)]
+        struct Alternate {
+            highest: bool,
+        }
+        impl Scheduler for Alternate {
+            fn pick(&mut self, state: &AbstractState, _cpu: CpuId) -> Vec<TaskId> {
+                let ready = state.ready_tasks().into_iter();
+                let picked = match self.highest {
+                    true => ready.max(),
+                    false => ready.min(),
+                };
+                self.highest = !self.highest;
+                picked.into_iter().collect()
+            }
+            fn key(&self) -> Vec<u8> {
+                vec![self.highest as u8]
+            }
+        }
+        let kml = "event spin { stop sched }
+            initial { tasks = [0, 1, 2]; current = 0; }
+            kernel { scheduler = alternate events = [spin] }";
+        let mut registry = Registry::default();
+        registry.register("alternate", |_| {
+            Some(Box::new(Alternate { highest: false }))
+        });
+        let model = lex_and_parse_kml(kml).unwrap();
+        let kernel = Kernel::from_model(model, None, &registry).unwrap();
+        // Picking the running task again changes only the policy, which is explored
+        let depths = explore(&kernel, 1);
+        assert_eq!(depths[1].states, 1);
+    }
+    #[test]
+    fn explore_invariant_test() {
+        let kernel = kernel(
+            "event timer { tick }
+            invariant early { time < 2 }
+            initial { tasks = [0]; current = 0; }
+            kernel {
+                scheduler = fifo
+                events = [timer]
+            }",
+        );
+        let depths = explore(&kernel, 3);
+        assert_eq!(depths.len(), 3);
+        assert_eq!(depths[2].states, 0);
+        assert_eq!(depths[2].violations, vec![vec!["timer", "timer"]]);
+    }
+}
@@ -0,0 +1,206 @@
+use super::Kernel;
+use crate::error::Error;
+use crate::parse::Resource;
+use crate::state::*;
+use std::collections::BTreeMap;
+
+/// Buffer capacity of a pipe created without an explicit one
+pub const PIPE_CAPACITY: u32 = 4;
+
+impl Kernel {
+    /// Open a regular file on the lowest free descriptor of current task
+    pub(super) fn open(&mut self, name: &str) -> Result<(), Error> {
+        let cur = self.current()?;
+        if !self.fds_available(cur, 1)? {
+            return Ok(());
+        }
+        self.install(cur, File::Regular(name.to_owned()))?;
+        Ok(())
+    }
+    /// Close a descriptor of current task
+    pub(super) fn close(&mut self, fd: Fd) -> Result<(), Error> {
+        let cur = self.current()?;
+        let file = self
+            .process_of_mut(cur)?
+            .fds
+            .remove(&fd)
+            .ok_or(Error::BadDescriptor)?;
+        self.drop_ref(&file);
+        Ok(())
+    }
+    /// Duplicate a descriptor of current task onto the lowest free one
+    pub(super) fn dup(&mut self, fd: Fd) -> Result<(), Error> {
+        let cur = self.current()?;
+        let file = self.file(cur, fd)?;
+        if !self.fds_available(cur, 1)? {
+            return Ok(());
+        }
+        self.add_ref(&file);
+        self.install(cur, file)?;
+        Ok(())
+    }
+    /// Create a pipe, its read end and write end take the two lowest free descriptors
+    pub(super) fn pipe(&mut self, capacity: u32) -> Result<(), Error> {
+        if capacity == 0 {
+            return Err(Error::BadArgument);
+        }
+        let cur = self.current()?;
+        if !self.fds_available(cur, 2)? {
+            return Ok(());
+        }
+        let id = (0..).find(|id| !self.state.pipes.contains_key(id)).unwrap();
+        self.state.pipes.insert(
+            id,
+            Pipe {
+                capacity,
+                buffered: 0,
+                readers: 1,
+                writers: 1,
+                read_waiters: Vec::new(),
+                write_waiters: Vec::new(),
+            },
+        );
+        self.install(cur, File::PipeRead(id))?;
+        self.install(cur, File::PipeWrite(id))?;
+        Ok(())
+    }
+    /// Read up to `size` from a descriptor of current task. Reading an empty pipe
+    /// blocks until data arrives or the last writer is closed.
+    pub(super) fn read(&mut self, fd: Fd, size: u32) -> Result<(), Error> {
+        let cur = self.current()?;
+        let id = match self.file(cur, fd)? {
+            File::Regular(_) => return Ok(()),
+            File::PipeRead(id) => id,
+            File::PipeWrite(_) => return Err(Error::BadDescriptor),
+        };
+        let pipe = self.state.pipes.get_mut(&id).unwrap();
+        if pipe.buffered > 0 || pipe.writers == 0 {
+            pipe.buffered -= size.min(pipe.buffered);
+            return self.flush(id);
+        }
+        self.suspend()?;
+        self.state
+            .pipes
+            .get_mut(&id)
+            .unwrap()
+            .read_waiters
+            .push((cur, size));
+        Ok(())
+    }
+    /// Write `size` to a descriptor of current task. Writes to a pipe are atomic,
+    /// a write that does not fit blocks until there is room.
+    pub(super) fn write(&mut self, fd: Fd, size: u32) -> Result<(), Error> {
+        let cur = self.current()?;
+        let id = match self.file(cur, fd)? {
+            File::Regular(_) => return Ok(()),
+            File::PipeWrite(id) => id,
+            File::PipeRead(_) => return Err(Error::BadDescriptor),
+        };
+        let pipe = self.state.pipes.get_mut(&id).unwrap();
+        if pipe.readers == 0 {
+            return Err(Error::BrokenPipe);
+        }
+        if size > pipe.capacity {
+            return Err(Error::BadArgument);
+        }
+        if pipe.write_waiters.is_empty() && pipe.buffered + size <= pipe.capacity {
+            pipe.buffered += size;
+            return self.flush(id);
+        }
+        self.suspend()?;
+        self.state
+            .pipes
+            .get_mut(&id)
+            .unwrap()
+            .write_waiters
+            .push((cur, size));
+        Ok(())
+    }
+    /// Close all descriptors of a torn down process
+    pub(super) fn close_all(&mut self, fds: &BTreeMap<Fd, File>) {
+        for file in fds.values() {
+            self.drop_ref(file);
+        }
+    }
+    /// Count a new descriptor referring to a file
+    pub(super) fn add_ref(&mut self, file: &File) {
+        match file {
+            File::Regular(_) => {}
+            File::PipeRead(id) => self.state.pipes.get_mut(id).unwrap().readers += 1,
+            File::PipeWrite(id) => self.state.pipes.get_mut(id).unwrap().writers += 1,
+        }
+    }
+    /// Forget a closed descriptor, a pipe is freed once both ends are closed
+    fn drop_ref(&mut self, file: &File) {
+        let (id, pipe) = match file {
+            File::Regular(_) => return,
+            File::PipeRead(id) | File::PipeWrite(id) => {
+                (*id, self.state.pipes.get_mut(id).unwrap())
+            }
+        };
+        match file {
+            File::PipeRead(_) => pipe.readers -= 1,
+            _ => pipe.writers -= 1,
+        }
+        if pipe.readers == 0 && pipe.writers == 0 {
+            self.state.pipes.remove(&id);
+        } else {
+            // Cannot fail as woken tasks exist
+            self.flush(id).unwrap();
+        }
+    }
+    /// Complete blocked reads and writes of a pipe as far as possible. Readers are
+    /// woken up at end of file, writers when all readers are gone and their data is
+    /// discarded.
+    fn flush(&mut self, id: PipeId) -> Result<(), Error> {
+        let mut woken = Vec::new();
+        let pipe = self.state.pipes.get_mut(&id).unwrap();
+        loop {
+            if let Some(&(reader, size)) = pipe.read_waiters.first() {
+                if pipe.buffered > 0 || pipe.writers == 0 {
+                    pipe.buffered -= size.min(pipe.buffered);
+                    pipe.read_waiters.remove(0);
+                    woken.push(reader);
+                    continue;
+                }
+            }
+            if let Some(&(writer, size)) = pipe.write_waiters.first() {
+                if pipe.readers == 0 || pipe.buffered + size <= pipe.capacity {
+                    if pipe.readers > 0 {
+                        pipe.buffered += size;
+                    }
+                    pipe.write_waiters.remove(0);
+                    woken.push(writer);
+                    continue;
+                }
+            }
+            break;
+        }
+        for id in woken {
+            self.wakeup(id)?;
+        }
+        Ok(())
+    }
+    /// Whether a task may open `count` more descriptors, recording the outcome
+    fn fds_available(&mut self, id: TaskId, count: u32) -> Result<bool, Error> {
+        let used = self.process_of(id)?.fds.len() as u32;
+        let available = self.within_limit(Resource::Fds, used + count);
+        self.set_errno(id, (!available).then_some(Errno::TooManyFiles));
+        Ok(available)
+    }
+    /// File a descriptor of a task refers to
+    fn file(&self, id: TaskId, fd: Fd) -> Result<File, Error> {
+        self.process_of(id)?
+            .fds
+            .get(&fd)
+            .cloned()
+            .ok_or(Error::BadDescriptor)
+    }
+    /// Put a file on the lowest free descriptor of a task
+    fn install(&mut self, id: TaskId, file: File) -> Result<Fd, Error> {
+        let fds = &mut self.process_of_mut(id)?.fds;
+        let fd = (0..).find(|fd| !fds.contains_key(fd)).unwrap();
+        fds.insert(fd, file);
+        Ok(fd)
+    }
+}
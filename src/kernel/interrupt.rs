@@ -0,0 +1,109 @@
+use super::Kernel;
+use crate::error::Error;
+use crate::parse::{BinaryOp, Expr};
+use crate::state::*;
+
+impl Kernel {
+    /// Disable interrupts on the cpu, interrupt events are deferred until enabled
+    pub(super) fn irq_off(&mut self) -> Result<(), Error> {
+        self.state.cpus[self.cpu].irq_enabled = false;
+        Ok(())
+    }
+    /// Enable interrupts on the cpu and run deferred interrupt events in name order,
+    /// stopping early if one of them disables interrupts again
+    pub(super) fn irq_on(&mut self) -> Result<(), Error> {
+        self.state.cpus[self.cpu].irq_enabled = true;
+        while self.state.cpus[self.cpu].irq_enabled {
+            let Some(name) = self.state.cpus[self.cpu].pending_irqs.pop_first() else {
+                break;
+            };
+            let actions = self
+                .events
+                .iter()
+                .find(|e| e.name() == name)
+                .ok_or(Error::EventNotFound)?
+                .actions()
+                .to_vec();
+            self.run_actions(actions)?;
+        }
+        Ok(())
+    }
+    pub(super) fn irq_enter(&mut self) -> Result<(), Error> {
+        self.state.cpus[self.cpu].irq_depth += 1;
+        Ok(())
+    }
+    pub(super) fn irq_exit(&mut self) -> Result<(), Error> {
+        let cpu = &mut self.state.cpus[self.cpu];
+        if cpu.irq_depth == 0 {
+            return Err(Error::BadArgument);
+        }
+        cpu.irq_depth -= 1;
+        Ok(())
+    }
+    /// Check the invariants placed at an action, or those checked after every event
+    /// if `at` is `None`
+    pub(super) fn check_invariants(&self, at: Option<&str>) -> Result<(), Error> {
+        for invariant in &self.invariants {
+            if invariant.at.as_deref() == at && self.eval(&invariant.expr) == 0 {
+                return Err(Error::InvariantViolated);
+            }
+        }
+        Ok(())
+    }
+    fn eval(&self, expr: &Expr) -> i64 {
+        match expr {
+            Expr::Number(n) => *n as i64,
+            Expr::Variable(name) => self.variable(name),
+            Expr::Not(expr) => (self.eval(expr) == 0) as i64,
+            Expr::Binary(lhs, op, rhs) => {
+                let (lhs, rhs) = (self.eval(lhs), self.eval(rhs));
+                let result = match op {
+                    BinaryOp::Eq => lhs == rhs,
+                    BinaryOp::Ne => lhs != rhs,
+                    BinaryOp::Lt => lhs < rhs,
+                    BinaryOp::Le => lhs <= rhs,
+                    BinaryOp::Gt => lhs > rhs,
+                    BinaryOp::Ge => lhs >= rhs,
+                    BinaryOp::And => lhs != 0 && rhs != 0,
+                    BinaryOp::Or => lhs != 0 || rhs != 0,
+                };
+                result as i64
+            }
+        }
+    }
+    /// Value of a variable, the parser only accepts known variables
+    fn variable(&self, name: &str) -> i64 {
+        let cpu = &self.state.cpus[self.cpu];
+        let count = |status: TaskStatus| {
+            self.state
+                .tasks
+                .iter()
+                .filter(|task| task.status == status)
+                .count() as i64
+        };
+        // Aggregates over tasks that have not exited, 0 if there are none
+        let stats = || {
+            self.state
+                .tasks
+                .iter()
+                .filter(|task| task.status != TaskStatus::Zombie)
+                .map(|task| &task.stats)
+        };
+        match name {
+            "irq_enabled" => cpu.irq_enabled as i64,
+            "irq_depth" => cpu.irq_depth as i64,
+            "time" => self.state.time as i64,
+            "tasks" => self.state.tasks.len() as i64,
+            "ready" => count(TaskStatus::Ready),
+            "blocked" => count(TaskStatus::Blocked),
+            "idle" => cpu.current.is_none() as i64,
+            "context_switches" => self.state.context_switches as i64,
+            "max_ready_time" => stats().map(|s| s.ready_time).max().unwrap_or(0) as i64,
+            "min_scheduled" => stats().map(|s| s.scheduled).min().unwrap_or(0) as i64,
+            "max_scheduled" => stats().map(|s| s.scheduled).max().unwrap_or(0) as i64,
+            "deadline_misses" => self.state.deadline_misses() as i64,
+            "wcet_overruns" => self.state.wcet_overruns() as i64,
+            _ => unreachable!(),
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use super::Kernel;
+use crate::error::Error;
+use crate::state::*;
+
+impl Kernel {
+    /// Send a payload through an endpoint, handing it to the first waiting receiver.
+    /// Current task blocks if no receiver waits, or until it gets a reply if `call`
+    /// is set.
+    pub(super) fn send(&mut self, name: &str, payload: u32, call: bool) -> Result<(), Error> {
+        let cur = self.current()?;
+        let receiver = self.state.endpoints.get_mut(name).and_then(|endpoint| {
+            (!endpoint.receivers.is_empty()).then(|| endpoint.receivers.remove(0))
+        });
+        let Some(receiver) = receiver else {
+            self.suspend()?;
+            self.state
+                .endpoints
+                .entry(name.to_owned())
+                .or_default()
+                .senders
+                .push((cur, payload, call));
+            return Ok(());
+        };
+        self.remove_if_idle(name);
+        self.deliver(cur, receiver, payload, call)?;
+        if call {
+            self.suspend()?;
+        }
+        Ok(())
+    }
+    /// Receive from the first sender waiting on an endpoint, or block current task
+    /// until one arrives. A task must reply to its last caller first.
+    pub(super) fn recv(&mut self, name: &str) -> Result<(), Error> {
+        let cur = self.current()?;
+        if self
+            .state
+            .task(cur)
+            .ok_or(Error::TaskNotFound)?
+            .reply_to
+            .is_some()
+        {
+            return Err(Error::BadArgument);
+        }
+        let sender = self.state.endpoints.get_mut(name).and_then(|endpoint| {
+            (!endpoint.senders.is_empty()).then(|| endpoint.senders.remove(0))
+        });
+        let Some((sender, payload, call)) = sender else {
+            self.suspend()?;
+            self.state
+                .endpoints
+                .entry(name.to_owned())
+                .or_default()
+                .receivers
+                .push(cur);
+            return Ok(());
+        };
+        self.remove_if_idle(name);
+        self.deliver(sender, cur, payload, call)?;
+        // A plain sender is done, a caller keeps waiting for the reply
+        if !call {
+            self.wakeup(sender)?;
+        }
+        Ok(())
+    }
+    /// Answer the caller current task received from
+    pub(super) fn reply(&mut self, payload: u32) -> Result<(), Error> {
+        let cur = self.current()?;
+        let caller = self
+            .state
+            .task_mut(cur)
+            .ok_or(Error::TaskNotFound)?
+            .reply_to
+            .take()
+            .ok_or(Error::ObjectNotFound)?;
+        self.deliver(cur, caller, payload, false)
+    }
+    /// Wake up the caller an exiting task owes a reply, without any message
+    pub(super) fn abandon_caller(&mut self, id: TaskId) -> Result<(), Error> {
+        match self.state.task_mut(id).unwrap().reply_to.take() {
+            Some(caller) => self.wakeup(caller),
+            None => Ok(()),
+        }
+    }
+    /// Store a message in the receiver, which becomes ready
+    fn deliver(
+        &mut self,
+        sender: TaskId,
+        receiver: TaskId,
+        payload: u32,
+        call: bool,
+    ) -> Result<(), Error> {
+        let task = self.state.task_mut(receiver).ok_or(Error::TaskNotFound)?;
+        task.message = Some(Message { sender, payload });
+        if call {
+            task.reply_to = Some(sender);
+        }
+        if task.status == TaskStatus::Blocked {
+            self.wakeup(receiver)?;
+        }
+        Ok(())
+    }
+    fn remove_if_idle(&mut self, name: &str) {
+        if self
+            .state
+            .endpoints
+            .get(name)
+            .is_some_and(|endpoint| endpoint.senders.is_empty() && endpoint.receivers.is_empty())
+        {
+            self.state.endpoints.remove(name);
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use super::Kernel;
+use crate::error::Error;
+use crate::parse::Resource;
+use crate::state::*;
+
+impl Kernel {
+    /// Map pages to the process of current task, frames are only allocated on page fault
+    pub(super) fn mmap(&mut self, start: Page, pages: u32, perm: Permission) -> Result<(), Error> {
+        self.frames()?;
+        let cur = self.current()?;
+        let memory = &mut self.process_of_mut(cur)?.memory;
+        // Areas never wrap around the address space
+        start.checked_add(pages).ok_or(Error::BadAddress)?;
+        let area = MemoryArea { start, pages, perm };
+        let overlaps = |other: &MemoryArea| other.start < area.end() && area.start < other.end();
+        if pages == 0 || overlaps(&memory.heap()) || memory.areas.iter().any(overlaps) {
+            return Err(Error::BadAddress);
+        }
+        let index = memory.areas.partition_point(|other| other.start < start);
+        memory.areas.insert(index, area);
+        Ok(())
+    }
+    /// Unmap pages of the process of current task, splitting areas that are partially unmapped
+    pub(super) fn munmap(&mut self, start: Page, pages: u32) -> Result<(), Error> {
+        self.frames()?;
+        let cur = self.current()?;
+        let memory = &mut self.process_of_mut(cur)?.memory;
+        let end = start.checked_add(pages).ok_or(Error::BadAddress)?;
+        let mut areas = Vec::new();
+        for area in memory.areas.drain(..) {
+            if area.start < start {
+                areas.push(MemoryArea {
+                    pages: area.pages.min(start - area.start),
+                    ..area.clone()
+                });
+            }
+            if area.end() > end {
+                let head = end.max(area.start);
+                areas.push(MemoryArea {
+                    start: head,
+                    pages: area.end() - head,
+                    ..area.clone()
+                });
+            }
+        }
+        memory.areas = areas;
+        let heap = memory.heap();
+        let released = memory
+            .resident
+            .range(start..end)
+            .copied()
+            .filter(|&page| !heap.contains(page))
+            .collect::<Vec<_>>();
+        for page in &released {
+            memory.resident.remove(page);
+        }
+        self.frames()?.free += released.len() as u32;
+        Ok(())
+    }
+    /// Handle an access of current task to a page. Allocates a frame if the access is
+    /// allowed, otherwise the task is killed and the cpu rescheduled. Exceeding the
+    /// frame limit or running out of frames fails with `Errno::NoMemory`.
+    pub(super) fn page_fault(&mut self, page: Page, access: Permission) -> Result<(), Error> {
+        self.frames()?;
+        let cur = self.current()?;
+        let memory = &self.process_of(cur)?.memory;
+        if !memory
+            .area(page)
+            .is_some_and(|area| area.perm.allows(access))
+        {
+            self.exit_task(cur)?;
+            return self.sched();
+        }
+        if memory.resident.contains(&page) {
+            return Ok(());
+        }
+        let used = memory.resident.len() as u32 + 1;
+        if !(self.within_limit(Resource::Frames, used) && self.alloc_frames(1)?) {
+            self.set_errno(cur, Some(Errno::NoMemory));
+            return Ok(());
+        }
+        self.set_errno(cur, None);
+        self.process_of_mut(cur)?.memory.resident.insert(page);
+        Ok(())
+    }
+    /// Set heap size of the process of current task, releasing frames of pages beyond it
+    pub(super) fn brk(&mut self, pages: u32) -> Result<(), Error> {
+        self.frames()?;
+        let cur = self.current()?;
+        let memory = &mut self.process_of_mut(cur)?.memory;
+        let end = HEAP_START.checked_add(pages).ok_or(Error::BadAddress)?;
+        if memory
+            .areas
+            .iter()
+            .any(|area| area.start < end && area.end() > HEAP_START)
+        {
+            return Err(Error::BadAddress);
+        }
+        let old_end = memory.heap().end();
+        memory.brk = pages;
+        let released = memory
+            .resident
+            .range(end..old_end.max(end))
+            .copied()
+            .collect::<Vec<_>>();
+        for page in &released {
+            memory.resident.remove(page);
+        }
+        self.frames()?.free += released.len() as u32;
+        Ok(())
+    }
+    /// Take frames from the pool, returns whether enough were free
+    pub(super) fn alloc_frames(&mut self, count: u32) -> Result<bool, Error> {
+        let frames = self.frames()?;
+        if frames.free < count {
+            return Ok(false);
+        }
+        frames.free -= count;
+        Ok(true)
+    }
+    /// Release the whole address space of a torn down process
+    pub(super) fn release_memory(&mut self, memory: &AddressSpace) {
+        if let Some(frames) = &mut self.state.frames {
+            frames.free += memory.resident.len() as u32;
+        }
+    }
+    fn frames(&mut self) -> Result<&mut FramePool, Error> {
+        self.state.frames.as_mut().ok_or(Error::MemoryNotModeled)
+    }
+}
@@ -0,0 +1,1692 @@
+use crate::error::Error;
+use crate::event::*;
+use crate::parse::{EventAction, InvariantDef, KernelConfig, Limit, Model, OnEmpty, Resource};
+use crate::scheduler::*;
+use crate::state::*;
+use fs::PIPE_CAPACITY;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::BTreeMap;
+
+mod fs;
+mod interrupt;
+mod ipc;
+mod memory;
+mod realtime;
+mod signal;
+
+/// Wait queue of tasks blocked in `wait` until one of their children exits.
+/// Its name is not an identifier, so no queue named in kml can collide with it.
+const WAIT_CHILD_QUEUE: &str = "<wait>";
+
+/// Ticks a task runs before `preempt` switches it out, unless configured
+const TIME_SLICE: u32 = 1;
+
+/// Resolves the nondeterministic choices of the model, such as which ready task to run
+#[derive(Debug, Clone)]
+enum Chooser {
+    /// Pick an option at random from the random number generator of the kernel
+    Random,
+    /// Follow a prefix of choices then take the first option, recording every
+    /// choice made as `(chosen, options)`. Used to enumerate all successors.
+    Script {
+        prefix: Vec<usize>,
+        trace: Vec<(usize, usize)>,
+    },
+}
+
+#[derive(Clone)]
+pub struct Kernel {
+    events: Vec<Event>,
+    scheduler: Box<dyn Scheduler>,
+    state: AbstractState,
+    shutdown_code: Option<i32>,
+    /// Cpu the executing event is targeted at
+    cpu: CpuId,
+    /// Task that ran on the cpu when the executing event started or was last picked,
+    /// switching back to it is not a context switch
+    outgoing: Option<TaskId>,
+    chooser: Chooser,
+    limits: BTreeMap<Resource, Limit>,
+    invariants: Vec<InvariantDef>,
+    on_empty: OnEmpty,
+    time_slice: u32,
+    /// Seed of `rng`, which makes every random choice when simulating
+    seed: u64,
+    rng: StdRng,
+}
+
+impl Kernel {
+    pub fn new(events: Vec<Event>, scheduler: Box<dyn Scheduler>, cpus: usize) -> Self {
+        let seed = rand::random();
+        let mut kernel = Self {
+            events,
+            scheduler,
+            state: AbstractState::new(cpus),
+            shutdown_code: None,
+            cpu: 0,
+            outgoing: None,
+            chooser: Chooser::Random,
+            limits: BTreeMap::new(),
+            invariants: Vec::new(),
+            on_empty: OnEmpty::default(),
+            time_slice: TIME_SLICE,
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        };
+        kernel.new_task(0).unwrap();
+        kernel.state.init_task = Some(0);
+        kernel.sched().unwrap();
+        kernel
+    }
+    /// Build the kernel model described by kml, starting from `initial_state` if given,
+    /// otherwise from the `initial` block, otherwise booting a single task. The scheduler
+    /// is looked up by name among those declared in kml, then in `registry`.
+    pub fn from_model(
+        model: Model,
+        initial_state: Option<AbstractState>,
+        registry: &Registry,
+    ) -> Result<Self, Error> {
+        // Config kernel
+        let mut enabled_events = None;
+        let mut scheduler = None;
+        let mut cpus = 1;
+        let mut frames = None;
+        let mut limits = BTreeMap::new();
+        let mut on_empty = OnEmpty::default();
+        let mut time_slice = TIME_SLICE;
+        let mut seed = None;
+        for config in &model.kernel_def.configs {
+            match config {
+                KernelConfig::Events(names) => enabled_events = Some(names),
+                KernelConfig::Scheduler(type_) => {
+                    // Schedulers declared in kml take precedence over registered ones
+                    let policy = model
+                        .policy_defs
+                        .iter()
+                        .find(|def| def.name.0 == type_.name());
+                    scheduler = Some(match policy {
+                        Some(def) => Box::new(Policy::new(def.clone())),
+                        None => registry.build(type_).ok_or(Error::BadKernelConfig)?,
+                    })
+                }
+                KernelConfig::Cpus(n) => cpus = *n as usize,
+                KernelConfig::Frames(n) => frames = Some(*n),
+                KernelConfig::Limit(resource, limit) => {
+                    limits.insert(*resource, *limit);
+                }
+                KernelConfig::OnEmpty(policy) => on_empty = *policy,
+                KernelConfig::TimeSlice(ticks) => time_slice = *ticks,
+                KernelConfig::Seed(n) => seed = Some(*n),
+            }
+        }
+        // Verification in parser ensures events and scheduler are not empty
+        let names = enabled_events.unwrap();
+        let scheduler = scheduler.unwrap();
+        let events = model
+            .event_defs
+            .into_iter()
+            .filter(|def| names.contains(&def.name))
+            .map(Event::new)
+            .collect::<Vec<_>>();
+        let initial_state = initial_state.or_else(|| {
+            model
+                .initial_def
+                .as_ref()
+                .map(|def| AbstractState::from_def(def, cpus))
+        });
+        let mut kernel = match initial_state {
+            Some(state) if state.cpus.len() != cpus => Err(Error::BadInitialState),
+            Some(state) => Self::with_state(events, scheduler, state),
+            None => Ok(Self::new(events, scheduler, cpus)),
+        }?;
+        if kernel.state.frames.is_none() {
+            kernel.state.frames = frames.map(|total| FramePool { total, free: total });
+        }
+        kernel.limits = limits;
+        kernel.invariants = model.invariant_defs;
+        kernel.on_empty = on_empty;
+        kernel.time_slice = time_slice;
+        if let Some(seed) = seed {
+            kernel.set_seed(seed);
+        }
+        Ok(kernel)
+    }
+    /// Create a kernel model that starts from a given state instead of booting.
+    /// Without a ready queue, ready tasks are queued in task order. Fails if the
+    /// scheduler cannot schedule a task of the state.
+    pub fn with_state(
+        events: Vec<Event>,
+        scheduler: Box<dyn Scheduler>,
+        mut state: AbstractState,
+    ) -> Result<Self, Error> {
+        state.queue_ready();
+        state.validate()?;
+        if !state.tasks.iter().all(|task| scheduler.admits(task)) {
+            return Err(Error::BadInitialState);
+        }
+        let seed = rand::random();
+        Ok(Self {
+            events,
+            scheduler,
+            state,
+            shutdown_code: None,
+            cpu: 0,
+            outgoing: None,
+            chooser: Chooser::Random,
+            limits: BTreeMap::new(),
+            invariants: Vec::new(),
+            on_empty: OnEmpty::default(),
+            time_slice: TIME_SLICE,
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        })
+    }
+    pub fn state(&self) -> &AbstractState {
+        &self.state
+    }
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+    /// Restart the random choices from a seed, the same seed and events always lead
+    /// to the same run
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+    /// Execute a given event, written as `name` or `name@cpu` to target a cpu other than 0.
+    /// The kernel is left unchanged if the event fails.
+    pub fn execute(&mut self, event: &str) -> Result<(), Error> {
+        let (name, cpu) = split_event(event)?;
+        let mut next = self.clone();
+        next.run(name, cpu)?;
+        *self = next;
+        Ok(())
+    }
+    /// All kernels that may result from executing an event on a cpu, one per
+    /// combination of nondeterministic choices. Empty if the event fails.
+    pub fn successors(&self, event: &str, cpu: CpuId) -> Vec<Kernel> {
+        self.outcomes(event, cpu)
+            .into_iter()
+            .filter_map(Result::ok)
+            .collect()
+    }
+    /// Outcome of executing an event on a cpu for every combination of
+    /// nondeterministic choices, including the failing ones
+    pub fn outcomes(&self, event: &str, cpu: CpuId) -> Vec<Result<Kernel, Error>> {
+        let mut outcomes = Vec::new();
+        let mut prefixes = vec![Vec::new()];
+        while let Some(prefix) = prefixes.pop() {
+            let mut next = self.clone();
+            next.chooser = Chooser::Script {
+                prefix: prefix.clone(),
+                trace: Vec::new(),
+            };
+            let result = next.run(event, cpu);
+            let Chooser::Script { trace, .. } =
+                std::mem::replace(&mut next.chooser, Chooser::Random)
+            else {
+                unreachable!()
+            };
+            // Branch on every alternative of the choices made after the prefix
+            for i in prefix.len()..trace.len() {
+                for alternative in trace[i].0 + 1..trace[i].1 {
+                    let mut branch = trace[..i]
+                        .iter()
+                        .map(|&(chosen, _)| chosen)
+                        .collect::<Vec<_>>();
+                    branch.push(alternative);
+                    prefixes.push(branch);
+                }
+            }
+            outcomes.push(result.map(|_| next));
+        }
+        outcomes
+    }
+    /// Key identifying the kernel for state space exploration, accounting left out
+    pub fn key(&self) -> (AbstractState, Vec<u8>, Option<i32>) {
+        (
+            self.state.without_stats(),
+            self.scheduler.key(),
+            self.shutdown_code,
+        )
+    }
+    fn run(&mut self, event: &str, cpu: CpuId) -> Result<(), Error> {
+        if cpu >= self.state.cpus.len() {
+            return Err(Error::CpuNotFound);
+        }
+        self.cpu = cpu;
+        self.outgoing = self.state.cpus[cpu].current;
+        let event = self
+            .events
+            .iter()
+            .find(|e| e.name() == event)
+            .cloned()
+            .ok_or(Error::EventNotFound)?;
+        for task in self.state.tasks.iter_mut() {
+            if task.status == TaskStatus::Running {
+                task.stats.running_events += 1;
+            }
+        }
+        if event.interrupt() && !self.state.cpus[cpu].irq_enabled {
+            self.state.cpus[cpu]
+                .pending_irqs
+                .insert(event.name().to_owned());
+        } else {
+            self.run_actions(event.actions().to_vec())?;
+        }
+        // Return to user mode, where a preemptive scheduler may switch tasks
+        if self.state.cpus[cpu].irq_enabled && self.scheduler.preempts(&self.state, cpu) {
+            self.stop()?;
+            self.sched()?;
+        }
+        self.deliver_signals()?;
+        self.check_invariants(None)
+    }
+    fn run_actions(&mut self, actions: Vec<EventAction>) -> Result<(), Error> {
+        for action in actions {
+            self.check_invariants(Some(action.keyword()))?;
+            match action {
+                EventAction::Shutdown => self.shutdown(0)?,
+                EventAction::NewTask(priority) => self.new_task(priority.unwrap_or(0))?,
+                EventAction::Exit => self.exit()?,
+                EventAction::Sched => self.sched()?,
+                EventAction::Stop => self.stop()?,
+                EventAction::Block(queue) => self.block(queue.as_ref())?,
+                EventAction::WakeupOne(queue) => self.wakeup_one(queue.as_ref())?,
+                EventAction::WakeupAll(queue) => self.wakeup_all(queue.as_ref())?,
+                EventAction::Fork => self.fork()?,
+                EventAction::Wait => self.wait()?,
+                EventAction::SetPrio(priority) => self.set_priority(priority)?,
+                EventAction::SetNice(nice) => self.set_nice(nice)?,
+                EventAction::SetTickets(tickets) => self.set_tickets(tickets)?,
+                EventAction::SemCreate(name, count) => self.sem_create(name.as_ref(), count)?,
+                EventAction::SemDown(name) => self.sem_down(name.as_ref())?,
+                EventAction::SemUp(name) => self.sem_up(name.as_ref())?,
+                EventAction::MutexLock(name) => self.mutex_lock(name.as_ref())?,
+                EventAction::MutexUnlock(name) => self.mutex_unlock(name.as_ref())?,
+                EventAction::Tick => self.tick()?,
+                EventAction::Sleep(ticks) => self.sleep(ticks)?,
+                EventAction::Mmap(start, pages, perm) => self.mmap(start, pages, perm)?,
+                EventAction::Munmap(start, pages) => self.munmap(start, pages)?,
+                EventAction::PageFault(page, access) => self.page_fault(page, access)?,
+                EventAction::Brk(pages) => self.brk(pages)?,
+                EventAction::Open(name) => self.open(name.as_ref())?,
+                EventAction::Close(fd) => self.close(fd)?,
+                EventAction::Dup(fd) => self.dup(fd)?,
+                EventAction::Pipe(capacity) => self.pipe(capacity.unwrap_or(PIPE_CAPACITY))?,
+                EventAction::Read(fd, size) => self.read(fd, size)?,
+                EventAction::Write(fd, size) => self.write(fd, size)?,
+                EventAction::Kill(task, sig) => self.kill(task, sig)?,
+                EventAction::SigMask(sigs) => self.sigmask(&sigs)?,
+                EventAction::SigAction(sig, action) => self.sigaction(sig, action)?,
+                EventAction::Send(endpoint, payload) => {
+                    self.send(endpoint.as_ref(), payload, false)?
+                }
+                EventAction::Recv(endpoint) => self.recv(endpoint.as_ref())?,
+                EventAction::Call(endpoint, payload) => {
+                    self.send(endpoint.as_ref(), payload, true)?
+                }
+                EventAction::Reply(payload) => self.reply(payload)?,
+                EventAction::IrqOff => self.irq_off()?,
+                EventAction::IrqOn => self.irq_on()?,
+                EventAction::IrqEnter => self.irq_enter()?,
+                EventAction::IrqExit => self.irq_exit()?,
+                EventAction::ThreadCreate => self.thread_create()?,
+                EventAction::ThreadExit => self.thread_exit()?,
+                EventAction::ExitGroup => self.exit_group()?,
+                EventAction::Preempt => self.preempt()?,
+                EventAction::Periodic(period, wcet, deadline) => {
+                    self.real_time(false, period, wcet, deadline)?
+                }
+                EventAction::Sporadic(period, wcet, deadline) => {
+                    self.real_time(true, period, wcet, deadline)?
+                }
+                EventAction::JobDone => self.job_done()?,
+                EventAction::Release(task) => self.release(task)?,
+            }
+        }
+        Ok(())
+    }
+    /// Print configurations
+    pub fn print_config(&self) {
+        println!("[Kernel Model]");
+        println!("Scheduler = {:?}", self.scheduler);
+        println!("Cpus = {}", self.state.cpus.len());
+        println!("On Empty = {:?}", self.on_empty);
+        println!("Time Slice = {}", self.time_slice);
+        println!("Seed = {}", self.seed);
+        println!(
+            "Limits = [{}]",
+            self.limits
+                .iter()
+                .map(|(resource, limit)| match limit.min == limit.max {
+                    true => format!("{:?} = {}", resource, limit.max),
+                    false => format!("{:?} = {}..{}", resource, limit.min, limit.max),
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        println!(
+            "Events = [{}]",
+            self.events
+                .iter()
+                .map(|e| e.name())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        println!(
+            "Invariants = [{}]",
+            self.invariants
+                .iter()
+                .map(|invariant| invariant.name.0.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        println!();
+    }
+    /// Print state
+    pub fn print_state(&self) {
+        println!("[Kernel State]");
+        println!("Time = {}", self.state.time);
+        for (id, cpu) in self.state.cpus.iter().enumerate() {
+            let irq = if cpu.irq_enabled { "on" } else { "off" };
+            let irqs = format!(
+                "(irq {}, depth {}, pending {:?})",
+                irq, cpu.irq_depth, cpu.pending_irqs
+            );
+            match cpu.current {
+                Some(task) => println!("Cpu {} = Task {} {}", id, task, irqs),
+                None => println!("Cpu {} = Idle {}", id, irqs),
+            }
+        }
+        println!("Tasks = {:?}", self.state.tasks);
+        println!("Ready Queue = {:?}", self.state.ready_queue);
+        println!("Processes = {:?}", self.state.processes);
+        println!("Wait Queues = {:?}", self.state.wait_queues);
+        println!("Semaphores = {:?}", self.state.semaphores);
+        println!("Mutexes = {:?}", self.state.mutexes);
+        if let Some(frames) = &self.state.frames {
+            println!("Frames = {}/{} free", frames.free, frames.total);
+        }
+        println!("Pipes = {:?}", self.state.pipes);
+        println!("Endpoints = {:?}", self.state.endpoints);
+        println!("Context Switches = {}", self.state.context_switches);
+        println!("Deadline Misses = {}", self.state.deadline_misses());
+        println!("WCET Overruns = {}", self.state.wcet_overruns());
+        println!();
+    }
+    /// Print state as a single line of json, along with the seed that reproduces the run
+    pub fn print_state_json(&self) {
+        let mut json = serde_json::to_value(&self.state).unwrap();
+        json["seed"] = self.seed.into();
+        println!("{}", json);
+    }
+
+    pub fn shutdown_code(&self) -> Option<i32> {
+        self.shutdown_code
+    }
+
+    // Built-in operations
+
+    /// Turn off the kernel model
+    pub fn shutdown(&mut self, code: i32) -> Result<(), Error> {
+        self.shutdown_code = Some(code);
+        Ok(())
+    }
+    /// Pick one of `options` choices
+    fn choose(&mut self, options: usize) -> usize {
+        self.choose_weighted(&vec![1; options])
+    }
+    /// Pick one of as many choices as `weights`, at random each one is picked with
+    /// a chance proportional to its weight
+    fn choose_weighted(&mut self, weights: &[u32]) -> usize {
+        let options = weights.len();
+        if options <= 1 {
+            return 0;
+        }
+        match &mut self.chooser {
+            Chooser::Random => {
+                let total = weights.iter().map(|&weight| weight as u64).sum();
+                let mut draw = self.rng.gen_range(0..total);
+                weights
+                    .iter()
+                    .position(|&weight| {
+                        let won = draw < weight as u64;
+                        draw = draw.saturating_sub(weight as u64);
+                        won
+                    })
+                    .unwrap()
+            }
+            Chooser::Script { prefix, trace } => {
+                let chosen = prefix.get(trace.len()).copied().unwrap_or(0);
+                trace.push((chosen, options));
+                chosen
+            }
+        }
+    }
+    /// Whether a resource may grow to `used` units, choosing between both outcomes
+    /// when the limit permits either
+    fn within_limit(&mut self, resource: Resource, used: u32) -> bool {
+        match self.limits.get(&resource).copied() {
+            Some(limit) if used > limit.max => false,
+            Some(limit) if used > limit.min => self.choose(2) == 0,
+            _ => true,
+        }
+    }
+    /// Record the outcome of an action subject to a limit in the calling task
+    fn set_errno(&mut self, id: TaskId, errno: Option<Errno>) {
+        if let Some(task) = self.state.task_mut(id) {
+            task.errno = errno;
+        }
+    }
+    /// Process of a task
+    fn process_of(&self, id: TaskId) -> Result<&Process, Error> {
+        self.state.process_of(id).ok_or(Error::TaskNotFound)
+    }
+    fn process_of_mut(&mut self, id: TaskId) -> Result<&mut Process, Error> {
+        self.state.process_of_mut(id).ok_or(Error::TaskNotFound)
+    }
+    /// Task running on the cpu the event is targeted at
+    fn current(&self) -> Result<TaskId, Error> {
+        self.state.cpus[self.cpu].current.ok_or(Error::CpuIdle)
+    }
+    /// Switch the cpu to a task given by scheduler, stopping current task if it is still
+    /// running. If no task is ready the `on_empty` policy applies.
+    fn sched(&mut self) -> Result<(), Error> {
+        let candidates = self.scheduler.pick(&self.state, self.cpu);
+        if candidates.is_empty() {
+            return match self.on_empty {
+                OnEmpty::Idle => {
+                    self.idle();
+                    Ok(())
+                }
+                OnEmpty::Shutdown(code) => self.shutdown(code),
+                OnEmpty::Error => Err(Error::NoReadyTask),
+            };
+        }
+        // Schedulers may be plugged in, so their answer is checked before it is trusted
+        if candidates
+            .iter()
+            .any(|id| !self.state.ready_queue.contains(id))
+        {
+            return Err(Error::BadSchedulerPick);
+        }
+        let weights = self.scheduler.weights(&self.state, &candidates);
+        if weights.len() != candidates.len() || weights.iter().all(|&weight| weight == 0) {
+            return Err(Error::BadSchedulerPick);
+        }
+        let id = candidates[self.choose_weighted(&weights)];
+        self.stop()?;
+        self.state.ready_queue.retain(|&ready| ready != id);
+        let task = self.state.task_mut(id).unwrap();
+        task.status = TaskStatus::Running;
+        task.stats.scheduled += 1;
+        task.slice_used = 0;
+        if self.outgoing != Some(id) {
+            self.state.context_switches += 1;
+        }
+        self.state.cpus[self.cpu].current = Some(id);
+        self.outgoing = Some(id);
+        Ok(())
+    }
+    /// Switch the cpu to its idle task unless current task is still running
+    fn idle(&mut self) {
+        if self.state.cpus[self.cpu].current.is_none() && self.outgoing.take().is_some() {
+            self.state.context_switches += 1;
+        }
+    }
+    /// Stop current task, moving it to the back of the ready queue and leaving the cpu
+    /// idle. Nothing to stop on an idle cpu.
+    fn stop(&mut self) -> Result<(), Error> {
+        let Ok(cur) = self.current() else {
+            return Ok(());
+        };
+        let task = self.state.task_mut(cur).ok_or(Error::TaskNotFound)?;
+        if task.status != TaskStatus::Running {
+            return Err(Error::TaskNotRunning);
+        }
+        task.slice_used = 0;
+        self.state.cpus[self.cpu].current = None;
+        self.wakeup(cur)
+    }
+    /// Stop current task and reschedule once its quantum is used up. An idle cpu
+    /// is rescheduled at once.
+    fn preempt(&mut self) -> Result<(), Error> {
+        if let Ok(cur) = self.current() {
+            let task = self.state.task_mut(cur).ok_or(Error::TaskNotFound)?;
+            let quantum = self.scheduler.quantum(task).unwrap_or(self.time_slice);
+            if task.slice_used < quantum {
+                return Ok(());
+            }
+            self.scheduler.expired(task);
+            self.stop()?;
+        }
+        self.sched()
+    }
+    /// Block current task on a wait queue
+    fn block(&mut self, queue: &str) -> Result<(), Error> {
+        let cur = self.suspend()?;
+        self.state
+            .wait_queues
+            .entry(queue.to_owned())
+            .or_default()
+            .push(cur);
+        Ok(())
+    }
+    /// Wake up the first task waiting on a queue
+    fn wakeup_one(&mut self, queue: &str) -> Result<(), Error> {
+        let Some(waiters) = self.state.wait_queues.get_mut(queue) else {
+            return Ok(());
+        };
+        let id = waiters.remove(0);
+        if waiters.is_empty() {
+            self.state.wait_queues.remove(queue);
+        }
+        self.wakeup(id)
+    }
+    /// Wake up all tasks waiting on a queue
+    fn wakeup_all(&mut self, queue: &str) -> Result<(), Error> {
+        for id in self.state.wait_queues.remove(queue).unwrap_or_default() {
+            self.wakeup(id)?;
+        }
+        Ok(())
+    }
+    /// Make a task ready, it joins the back of the ready queue
+    fn wakeup(&mut self, id: TaskId) -> Result<(), Error> {
+        self.state
+            .tasks
+            .iter_mut()
+            .find(|task| task.id == id)
+            .ok_or(Error::TaskNotFound)?
+            .status = TaskStatus::Ready;
+        self.state.ready_queue.push(id);
+        Ok(())
+    }
+    /// Create a semaphore
+    fn sem_create(&mut self, name: &str, count: u32) -> Result<(), Error> {
+        if self.state.semaphores.contains_key(name) {
+            return Err(Error::ObjectExists);
+        }
+        self.state.semaphores.insert(
+            name.to_owned(),
+            Semaphore {
+                count,
+                waiters: Vec::new(),
+            },
+        );
+        Ok(())
+    }
+    /// Decrease a semaphore, or block current task if it is zero
+    fn sem_down(&mut self, name: &str) -> Result<(), Error> {
+        let sem = self
+            .state
+            .semaphores
+            .get_mut(name)
+            .ok_or(Error::ObjectNotFound)?;
+        if sem.count > 0 {
+            sem.count -= 1;
+            return Ok(());
+        }
+        let cur = self.suspend()?;
+        self.state
+            .semaphores
+            .get_mut(name)
+            .unwrap()
+            .waiters
+            .push(cur);
+        Ok(())
+    }
+    /// Wake up the first waiter of a semaphore, or increase it if nobody waits
+    fn sem_up(&mut self, name: &str) -> Result<(), Error> {
+        let sem = self
+            .state
+            .semaphores
+            .get_mut(name)
+            .ok_or(Error::ObjectNotFound)?;
+        if sem.waiters.is_empty() {
+            sem.count += 1;
+            return Ok(());
+        }
+        let id = sem.waiters.remove(0);
+        self.wakeup(id)
+    }
+    /// Acquire a mutex, or block current task if it is owned
+    fn mutex_lock(&mut self, name: &str) -> Result<(), Error> {
+        let cur = self.current()?;
+        let mutex = self.state.mutexes.entry(name.to_owned()).or_insert(Mutex {
+            owner: None,
+            waiters: Vec::new(),
+        });
+        match mutex.owner {
+            None => {
+                mutex.owner = Some(cur);
+                Ok(())
+            }
+            Some(owner) if owner == cur => Err(Error::Deadlock),
+            Some(_) => {
+                self.suspend()?;
+                self.state.mutexes.get_mut(name).unwrap().waiters.push(cur);
+                Ok(())
+            }
+        }
+    }
+    /// Release a mutex owned by current task, handing it off to the first waiter
+    fn mutex_unlock(&mut self, name: &str) -> Result<(), Error> {
+        let mutex = self
+            .state
+            .mutexes
+            .get_mut(name)
+            .ok_or(Error::ObjectNotFound)?;
+        if mutex.owner != self.state.cpus[self.cpu].current {
+            return Err(Error::NotOwner);
+        }
+        self.release_mutex(name)
+    }
+    /// Hand a mutex off to its first waiter, or remove it if nobody waits
+    fn release_mutex(&mut self, name: &str) -> Result<(), Error> {
+        let mutex = self
+            .state
+            .mutexes
+            .get_mut(name)
+            .ok_or(Error::ObjectNotFound)?;
+        if mutex.waiters.is_empty() {
+            self.state.mutexes.remove(name);
+            return Ok(());
+        }
+        let id = mutex.waiters.remove(0);
+        mutex.owner = Some(id);
+        self.wakeup(id)
+    }
+    /// Advance the clock, use up time slices and wake up sleepers whose time has come
+    fn tick(&mut self) -> Result<(), Error> {
+        self.state.time += 1;
+        for task in self.state.tasks.iter_mut() {
+            match task.status {
+                TaskStatus::Ready => task.stats.ready_time += 1,
+                TaskStatus::Running => task.slice_used += 1,
+                _ => {}
+            }
+        }
+        self.scheduler.ticked(&mut self.state);
+        self.account_jobs();
+        let mut expired = self
+            .state
+            .tasks
+            .iter()
+            .filter_map(|task| task.wake_at.map(|time| (time, task.id)))
+            .filter(|&(time, _)| time <= self.state.time)
+            .collect::<Vec<_>>();
+        expired.sort();
+        for (time, id) in expired {
+            let task = self.state.task_mut(id).unwrap();
+            task.wake_at = None;
+            // A periodic task waiting for its next release starts a new job
+            if let Some(rt) = task.rt.as_mut().filter(|rt| rt.done) {
+                rt.start_job(time);
+            }
+            self.wakeup(id)?;
+        }
+        Ok(())
+    }
+    /// Block current task until the clock advances by `ticks`
+    fn sleep(&mut self, ticks: u32) -> Result<(), Error> {
+        let cur = self.suspend()?;
+        self.state.task_mut(cur).unwrap().wake_at = Some(self.state.time + ticks as u64);
+        Ok(())
+    }
+    /// Mark current task as blocked, leaving the cpu idle, and return its id. The caller
+    /// records what the task waits for. Blocking actions never reschedule themselves,
+    /// the event picks the next task with `sched`.
+    fn suspend(&mut self) -> Result<TaskId, Error> {
+        let cur = self.current()?;
+        let task = self.state.task_mut(cur).ok_or(Error::TaskNotFound)?;
+        if task.status != TaskStatus::Running {
+            return Err(Error::TaskNotRunning);
+        }
+        task.status = TaskStatus::Blocked;
+        task.slice_used = 0;
+        self.scheduler.blocked(task);
+        self.state.cpus[self.cpu].current = None;
+        Ok(cur)
+    }
+    /// Remove a task from whatever it is blocked on
+    fn dequeue(&mut self, id: TaskId) {
+        for waiters in self.state.wait_queues.values_mut() {
+            waiters.retain(|&waiter| waiter != id);
+        }
+        for sem in self.state.semaphores.values_mut() {
+            sem.waiters.retain(|&waiter| waiter != id);
+        }
+        for mutex in self.state.mutexes.values_mut() {
+            mutex.waiters.retain(|&waiter| waiter != id);
+        }
+        for pipe in self.state.pipes.values_mut() {
+            pipe.read_waiters.retain(|&(waiter, _)| waiter != id);
+            pipe.write_waiters.retain(|&(waiter, _)| waiter != id);
+        }
+        for endpoint in self.state.endpoints.values_mut() {
+            endpoint.senders.retain(|&(waiter, _, _)| waiter != id);
+            endpoint.receivers.retain(|&waiter| waiter != id);
+        }
+        self.state
+            .endpoints
+            .retain(|_, endpoint| !endpoint.senders.is_empty() || !endpoint.receivers.is_empty());
+        for task in self.state.tasks.iter_mut() {
+            if task.reply_to == Some(id) {
+                task.reply_to = None;
+            }
+        }
+        if let Some(task) = self.state.task_mut(id) {
+            task.wake_at = None;
+        }
+        self.state
+            .wait_queues
+            .retain(|_, waiters| !waiters.is_empty());
+    }
+    /// Exit current task, terminating its whole process
+    fn exit(&mut self) -> Result<(), Error> {
+        self.exit_task(self.current()?)
+    }
+    /// Terminate the process of a task with all its threads
+    fn exit_task(&mut self, id: TaskId) -> Result<(), Error> {
+        let threads = self
+            .state
+            .process_of(id)
+            .ok_or(Error::TaskNotFound)?
+            .threads
+            .clone();
+        for thread in threads {
+            self.exit_thread(thread)?;
+        }
+        Ok(())
+    }
+    /// Exit a single thread, the last thread exiting tears down the process. The first
+    /// thread of a process stays as a zombie until its parent reaps it, other threads
+    /// are released at once.
+    fn exit_thread(&mut self, id: TaskId) -> Result<(), Error> {
+        let process = self.state.task(id).ok_or(Error::TaskNotFound)?.process;
+        self.dequeue(id);
+        self.abandon_caller(id)?;
+        // Mutexes held by the thread pass to their waiters
+        let owned = self
+            .state
+            .mutexes
+            .iter()
+            .filter(|(_, mutex)| mutex.owner == Some(id))
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+        for name in owned {
+            self.release_mutex(&name)?;
+        }
+        self.state.ready_queue.retain(|&ready| ready != id);
+        for cpu in self.state.cpus.iter_mut() {
+            if cpu.current == Some(id) {
+                cpu.current = None;
+            }
+        }
+        let threads = &mut self.state.processes.get_mut(&process).unwrap().threads;
+        threads.retain(|&thread| thread != id);
+        let last = threads.is_empty();
+        if id == process {
+            let task = self.state.task_mut(id).unwrap();
+            task.status = TaskStatus::Zombie;
+            task.slice_used = 0;
+        } else {
+            self.state.tasks.retain(|task| task.id != id);
+        }
+        if last {
+            let resources = self.state.processes.remove(&process).unwrap();
+            self.release_memory(&resources.memory);
+            self.close_all(&resources.fds);
+            self.exit_process(process)?;
+        }
+        Ok(())
+    }
+    /// A process was torn down, its children are reparented to init and its first
+    /// thread is left for the parent to reap
+    fn exit_process(&mut self, id: ProcessId) -> Result<(), Error> {
+        let task = self.state.task_mut(id).unwrap();
+        let children = std::mem::take(&mut task.children);
+        let has_parent = task.parent.is_some();
+        if self.state.init_task == Some(id) {
+            self.state.init_task = None;
+        }
+        // Orphans are reparented to init
+        let init = self.state.init_task;
+        for &child in &children {
+            self.state.task_mut(child).unwrap().parent = init;
+            if let Some(init) = init {
+                self.state.task_mut(init).unwrap().children.push(child);
+            }
+        }
+        for child in children {
+            if self.state.exited(child) {
+                self.notify_parent(child)?;
+            }
+        }
+        if has_parent {
+            self.notify_parent(id)
+        } else {
+            self.state.tasks.retain(|task| task.id != id);
+            Ok(())
+        }
+    }
+    /// Exit current thread
+    fn thread_exit(&mut self) -> Result<(), Error> {
+        self.exit_thread(self.current()?)
+    }
+    /// Terminate all threads of the process of current task, running, ready or blocked
+    fn exit_group(&mut self) -> Result<(), Error> {
+        let cur = self.current()?;
+        self.exit_task(cur)
+    }
+    /// Create a thread in the process of current task, it starts as ready with the
+    /// priority, nice value, tickets and signal mask and dispositions of its creator.
+    /// Exceeding the task limit fails with `Errno::Again`.
+    fn thread_create(&mut self) -> Result<(), Error> {
+        let cur = self.current()?;
+        if !self.within_limit(Resource::Tasks, self.state.tasks.len() as u32 + 1) {
+            self.set_errno(cur, Some(Errno::Again));
+            return Ok(());
+        }
+        let creator = self.state.task(cur).ok_or(Error::TaskNotFound)?.clone();
+        let id = self.state.free_task_id();
+        let mut task = TaskControlBlock::new(id);
+        task.process = creator.process;
+        task.priority = creator.priority;
+        task.nice = creator.nice;
+        task.tickets = creator.tickets;
+        task.sigmask = creator.sigmask;
+        task.sigactions = creator.sigactions;
+        self.state.tasks.push(task);
+        self.state.ready_queue.push(id);
+        self.scheduler.created(&mut self.state, id);
+        self.state
+            .processes
+            .get_mut(&creator.process)
+            .unwrap()
+            .threads
+            .push(id);
+        self.set_errno(cur, None);
+        Ok(())
+    }
+    /// A child process exited, reap it if its parent is waiting
+    fn notify_parent(&mut self, child: TaskId) -> Result<(), Error> {
+        let parent = self.state.task(child).ok_or(Error::TaskNotFound)?.parent;
+        match parent {
+            None => self.reap(child),
+            Some(parent) => {
+                // Any thread of the parent process may be waiting
+                let waiter = self
+                    .state
+                    .wait_queues
+                    .get(WAIT_CHILD_QUEUE)
+                    .and_then(|waiters| {
+                        waiters
+                            .iter()
+                            .copied()
+                            .find(|&id| self.state.task(id).unwrap().process == parent)
+                    });
+                if let Some(waiter) = waiter {
+                    self.dequeue(waiter);
+                    self.wakeup(waiter)?;
+                    self.reap(child)?;
+                }
+                Ok(())
+            }
+        }
+    }
+    /// Release a zombie task
+    fn reap(&mut self, id: TaskId) -> Result<(), Error> {
+        let parent = self.state.task(id).ok_or(Error::TaskNotFound)?.parent;
+        if let Some(parent) = parent.and_then(|parent| self.state.task_mut(parent)) {
+            parent.children.retain(|&child| child != id);
+        }
+        self.state.tasks.retain(|task| task.id != id);
+        Ok(())
+    }
+    /// Reap an exited child of the process of current task, or block until one exits
+    fn wait(&mut self) -> Result<(), Error> {
+        let cur = self.current()?;
+        let process = self.state.task(cur).ok_or(Error::TaskNotFound)?.process;
+        let children = &self.state.task(process).unwrap().children;
+        let zombie = children
+            .iter()
+            .copied()
+            .find(|&child| self.state.exited(child));
+        match zombie {
+            Some(zombie) => self.reap(zombie),
+            // Nothing to wait for
+            None if children.is_empty() => Ok(()),
+            None => self.block(WAIT_CHILD_QUEUE),
+        }
+    }
+    /// Fork current task into a single-threaded child of its process. The child starts
+    /// as ready and inherits priority, nice, tickets, a copy of the address space, the file
+    /// descriptors and the signal mask and dispositions, but no pending signals.
+    /// Exceeding the task limit fails with `Errno::Again`, and running out of frames for
+    /// the copy or exceeding the frame limit with `Errno::NoMemory`.
+    fn fork(&mut self) -> Result<(), Error> {
+        let cur = self.current()?;
+        if !self.within_limit(Resource::Tasks, self.state.tasks.len() as u32 + 1) {
+            self.set_errno(cur, Some(Errno::Again));
+            return Ok(());
+        }
+        let origin = self.state.task(cur).ok_or(Error::TaskNotFound)?.clone();
+        let resources = self.process_of(cur)?.clone();
+        let resident = resources.memory.resident.len() as u32;
+        if resident > 0
+            && !(self.within_limit(Resource::Frames, resident) && self.alloc_frames(resident)?)
+        {
+            self.set_errno(cur, Some(Errno::NoMemory));
+            return Ok(());
+        }
+        for file in resources.fds.values() {
+            self.add_ref(file);
+        }
+        let child = self.spawn(Some(origin.process), origin.priority);
+        let task = self.state.task_mut(child).unwrap();
+        task.nice = origin.nice;
+        task.tickets = origin.tickets;
+        task.sigmask = origin.sigmask;
+        task.sigactions = origin.sigactions;
+        let process = self.state.processes.get_mut(&child).unwrap();
+        process.memory = resources.memory;
+        process.fds = resources.fds;
+        self.state
+            .task_mut(origin.process)
+            .unwrap()
+            .children
+            .push(child);
+        self.set_errno(cur, None);
+        Ok(())
+    }
+    /// Add a new task. Exceeding the task limit creates nothing and fails with
+    /// `Errno::Again` in the running task, if any.
+    fn new_task(&mut self, priority: i32) -> Result<(), Error> {
+        let errno = if self.within_limit(Resource::Tasks, self.state.tasks.len() as u32 + 1) {
+            self.spawn(None, priority);
+            None
+        } else {
+            Some(Errno::Again)
+        };
+        if let Some(cur) = self.state.cpus[self.cpu].current {
+            self.set_errno(cur, errno);
+        }
+        Ok(())
+    }
+    /// Set priority of current task
+    fn set_priority(&mut self, priority: i32) -> Result<(), Error> {
+        let cur = self.current()?;
+        self.state
+            .task_mut(cur)
+            .ok_or(Error::TaskNotFound)?
+            .priority = priority;
+        Ok(())
+    }
+    /// Set nice value of current task
+    fn set_nice(&mut self, nice: i32) -> Result<(), Error> {
+        if !(-20..=19).contains(&nice) {
+            return Err(Error::BadArgument);
+        }
+        let cur = self.current()?;
+        self.state.task_mut(cur).ok_or(Error::TaskNotFound)?.nice = nice;
+        Ok(())
+    }
+    /// Set tickets of current task
+    fn set_tickets(&mut self, tickets: u32) -> Result<(), Error> {
+        if tickets == 0 {
+            return Err(Error::BadArgument);
+        }
+        let cur = self.current()?;
+        self.state.task_mut(cur).ok_or(Error::TaskNotFound)?.tickets = tickets;
+        Ok(())
+    }
+    /// Create a ready task in a new process and return its id
+    fn spawn(&mut self, parent: Option<ProcessId>, priority: i32) -> TaskId {
+        let id = self.state.free_task_id();
+        let mut task = TaskControlBlock::new(id);
+        task.parent = parent;
+        task.priority = priority;
+        self.state.tasks.push(task);
+        self.state.ready_queue.push(id);
+        self.scheduler.created(&mut self.state, id);
+        self.state.processes.insert(
+            id,
+            Process {
+                threads: vec![id],
+                ..Default::default()
+            },
+        );
+        id
+    }
+}
+
+/// Split an event written as `name` or `name@cpu` into its name and cpu
+pub fn split_event(event: &str) -> Result<(&str, CpuId), Error> {
+    match event.split_once('@') {
+        Some((name, cpu)) => Ok((name, cpu.parse().map_err(|_| Error::CpuNotFound)?)),
+        None => Ok((event, 0)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::lex_and_parse_kml;
+
+    fn kernel(kml: &str) -> Kernel {
+        Kernel::from_model(lex_and_parse_kml(kml).unwrap(), None, &Registry::default()).unwrap()
+    }
+
+    #[test]
+    fn wait_queue_test() {
+        let mut kernel = kernel(
+            "event read { block(io) sched }
+            event irq { wakeup_one(io) }
+            event flush { wakeup_all(io) }
+            initial { tasks = [0, 1, 2]; current = 0; }
+            kernel { scheduler = fifo events = [read, irq, flush] }",
+        );
+        kernel.execute("read").unwrap();
+        kernel.execute("read").unwrap();
+        assert_eq!(kernel.state().cpus[0].current, Some(2));
+        assert_eq!(kernel.state().wait_queues["io"], vec![0, 1]);
+        assert_eq!(kernel.state().tasks[0].status, TaskStatus::Blocked);
+
+        kernel.execute("irq").unwrap();
+        assert_eq!(kernel.state().wait_queues["io"], vec![1]);
+        assert_eq!(kernel.state().tasks[0].status, TaskStatus::Ready);
+
+        kernel.execute("flush").unwrap();
+        assert!(kernel.state().wait_queues.is_empty());
+        assert_eq!(kernel.state().tasks[1].status, TaskStatus::Ready);
+    }
+
+    #[test]
+    fn process_hierarchy_test() {
+        let mut kernel = kernel(
+            "event clone { fork }
+            event join { wait sched }
+            event end { exit sched }
+            event read { block(io) sched }
+            event irq { wakeup_one(io) }
+            kernel { scheduler = fifo events = [clone, join, end, read, irq] }",
+        );
+        // 0 forks 1, 1 forks 2
+        kernel.execute("clone").unwrap();
+        kernel.execute("join").unwrap();
+        assert_eq!(kernel.state().cpus[0].current, Some(1));
+        assert_eq!(kernel.state().wait_queues[WAIT_CHILD_QUEUE], vec![0]);
+        kernel.execute("clone").unwrap();
+        // 1 exits, 2 is reparented to init and 0 reaps 1
+        kernel.execute("end").unwrap();
+        assert!(kernel.state().task(1).is_none());
+        assert_eq!(kernel.state().task(2).unwrap().parent, Some(0));
+        assert_eq!(kernel.state().task(0).unwrap().children, vec![2]);
+        assert_eq!(kernel.state().cpus[0].current, Some(0));
+        // 2 exits without anyone waiting and stays a zombie
+        kernel.execute("read").unwrap();
+        kernel.execute("irq").unwrap();
+        kernel.execute("end").unwrap();
+        assert_eq!(kernel.state().task(2).unwrap().status, TaskStatus::Zombie);
+        kernel.execute("join").unwrap();
+        assert!(kernel.state().task(2).is_none());
+        assert!(kernel.state().task(0).unwrap().children.is_empty());
+    }
+
+    #[test]
+    fn sync_test() {
+        let mut kernel = kernel(
+            "event setup { sem_create(items, 1) }
+            event take { sem_down(items) }
+            event consume { sem_down(items) sched }
+            event produce { sem_up(items) }
+            event grab { mutex_lock(m) }
+            event lock { mutex_lock(m) sched }
+            event unlock { mutex_unlock(m) }
+            initial { tasks = [0, 1, 2]; current = 0; }
+            kernel { scheduler = fifo events = [setup, take, consume, produce, grab, lock, unlock] }",
+        );
+        kernel.execute("setup").unwrap();
+        kernel.execute("take").unwrap();
+        assert_eq!(kernel.state().semaphores["items"].count, 0);
+        // 0 blocks on the semaphore, 1 blocks on the mutex held by 0
+        kernel.execute("grab").unwrap();
+        kernel.execute("consume").unwrap();
+        assert_eq!(kernel.state().cpus[0].current, Some(1));
+        assert_eq!(kernel.execute("unlock"), Err(Error::NotOwner));
+        kernel.execute("lock").unwrap();
+        assert_eq!(kernel.state().cpus[0].current, Some(2));
+        assert_eq!(kernel.state().mutexes["m"].waiters, vec![1]);
+        kernel.execute("produce").unwrap();
+        assert_eq!(kernel.state().task(0).unwrap().status, TaskStatus::Ready);
+        assert!(kernel.state().semaphores["items"].waiters.is_empty());
+    }
+    #[test]
+    fn mutex_owner_exit_test() {
+        // A mutex whose owner exits passes to its first waiter
+        let mut kernel = kernel(
+            "event lock { mutex_lock(m) sched }
+            event quit { exit sched }
+            initial { tasks = [0, 1]; current = 1; mutex(m) = 0; }
+            kernel { scheduler = fifo events = [lock, quit] }",
+        );
+        kernel.execute("lock").unwrap();
+        assert_eq!(kernel.state().cpus[0].current, Some(0));
+        kernel.execute("quit").unwrap();
+        assert_eq!(kernel.state().mutexes["m"].owner, Some(1));
+        assert_eq!(kernel.state().cpus[0].current, Some(1));
+        assert!(kernel.state().validate().is_ok());
+    }
+
+    #[test]
+    fn priority_test() {
+        let mut kernel = kernel(
+            "event spawn { newtask(3) }
+            event renice { setprio(5) setnice(-5) }
+            event clone { fork }
+            kernel { scheduler = fifo events = [spawn, renice, clone] }",
+        );
+        kernel.execute("spawn").unwrap();
+        kernel.execute("renice").unwrap();
+        kernel.execute("clone").unwrap();
+        let priorities = kernel
+            .state()
+            .tasks
+            .iter()
+            .map(|task| (task.priority, task.nice))
+            .collect::<Vec<_>>();
+        assert_eq!(priorities, vec![(5, -5), (3, 0), (5, -5)]);
+    }
+
+    #[test]
+    fn multi_core_test() {
+        let mut kernel = kernel(
+            "event timer { stop sched }
+            initial { tasks = [0, 1, 2]; current = 0; }
+            kernel { scheduler = random cpus = 2 events = [timer] }",
+        );
+        assert_eq!(kernel.state().cpus[1].current, None);
+        // Cpu 1 may pick either ready task, cpu 0 may also keep its task
+        assert_eq!(kernel.successors("timer", 1).len(), 2);
+        assert_eq!(kernel.successors("timer", 0).len(), 3);
+        kernel.execute("timer@1").unwrap();
+        let running = kernel.state().cpus[1].current.unwrap();
+        assert_ne!(running, 0);
+        assert_eq!(
+            kernel.state().task(running).unwrap().status,
+            TaskStatus::Running
+        );
+        assert_eq!(kernel.execute("timer@2"), Err(Error::CpuNotFound));
+        assert!(kernel.state().validate().is_ok());
+    }
+    #[test]
+    fn multi_core_switch_test() {
+        // A stopped task leaves its cpu, and `sched` stops the task it replaces
+        let mut kernel = kernel(
+            "event yield { stop }
+            event run { sched }
+            initial { tasks = [0, 1]; current = [0, 1]; }
+            kernel { scheduler = fifo cpus = 2 events = [yield, run] }",
+        );
+        kernel.execute("yield@0").unwrap();
+        assert_eq!(kernel.state().cpus[0].current, None);
+        kernel.execute("run@1").unwrap();
+        assert_eq!(kernel.state().cpus[1].current, Some(0));
+        assert_eq!(kernel.state().task(1).unwrap().status, TaskStatus::Ready);
+        assert!(kernel.state().validate().is_ok());
+    }
+
+    #[test]
+    fn sleep_test() {
+        let mut kernel = kernel(
+            "event timer { tick stop sched }
+            event nap { sleep(2) sched }
+            initial { tasks = [0, 1]; current = 0; }
+            kernel { scheduler = fifo events = [timer, nap] }",
+        );
+        kernel.execute("nap").unwrap();
+        assert_eq!(kernel.state().task(0).unwrap().wake_at, Some(2));
+        kernel.execute("timer").unwrap();
+        assert_eq!(kernel.state().task(0).unwrap().status, TaskStatus::Blocked);
+        kernel.execute("timer").unwrap();
+        assert_eq!(kernel.state().time, 2);
+        assert_eq!(kernel.state().task(0).unwrap().wake_at, None);
+        assert_eq!(kernel.state().cpus[0].current, Some(0));
+    }
+
+    #[test]
+    fn memory_test() {
+        let mut kernel = kernel(
+            "event map { mmap(0, 4, rw) }
+            event unmap { munmap(1, 2) }
+            event touch0 { page_fault(0, w) }
+            event touch1 { page_fault(1, r) }
+            event touch2 { page_fault(2, r) }
+            event exec0 { page_fault(0, x) }
+            event grow { brk(1) }
+            event touch_heap { page_fault(4096, w) }
+            event shrink { brk(0) }
+            event wrap { mmap(4294967295, 2, r) }
+            event wrap_heap { brk(4294967295) }
+            initial { tasks = [0, 1]; current = 0; }
+            kernel {
+                scheduler = fifo
+                frames = 2
+                events = [map, unmap, touch0, touch1, touch2, exec0, grow, touch_heap, shrink,
+                    wrap, wrap_heap]
+            }",
+        );
+        kernel.execute("map").unwrap();
+        assert_eq!(kernel.execute("map"), Err(Error::BadAddress));
+        assert_eq!(kernel.execute("wrap"), Err(Error::BadAddress));
+        assert_eq!(kernel.execute("wrap_heap"), Err(Error::BadAddress));
+        kernel.execute("touch0").unwrap();
+        kernel.execute("touch1").unwrap();
+        kernel.execute("touch2").unwrap();
+        assert_eq!(kernel.state().task(0).unwrap().errno, Some(Errno::NoMemory));
+        kernel.execute("unmap").unwrap();
+        let memory = &kernel.state().processes[&0].memory;
+        assert_eq!(memory.areas.len(), 2);
+        assert_eq!((memory.areas[1].start, memory.areas[1].pages), (3, 1));
+        assert_eq!(kernel.state().frames.as_ref().unwrap().free, 1);
+        kernel.execute("grow").unwrap();
+        kernel.execute("touch_heap").unwrap();
+        assert_eq!(kernel.state().frames.as_ref().unwrap().free, 0);
+        kernel.execute("shrink").unwrap();
+        assert_eq!(kernel.state().frames.as_ref().unwrap().free, 1);
+        // Executing a non-executable page kills the task and releases its frames
+        kernel.execute("exec0").unwrap();
+        assert!(kernel.state().task(0).is_none());
+        assert_eq!(kernel.state().cpus[0].current, Some(1));
+        assert_eq!(kernel.state().frames.as_ref().unwrap().free, 2);
+    }
+
+    #[test]
+    fn pipe_test() {
+        let mut kernel = kernel(
+            "event mkpipe { pipe(2) }
+            event clone { fork }
+            event drain { read(0, 1) }
+            event consume { read(0, 1) sched }
+            event fill { write(1, 2) }
+            event produce { write(1, 2) sched }
+            event close_read { close(0) }
+            event close_write { close(1) }
+            event end { exit sched }
+            initial { tasks = [0]; current = 0; }
+            kernel {
+                scheduler = fifo
+                events = [mkpipe, clone, drain, consume, fill, produce, close_read, close_write, end]
+            }",
+        );
+        kernel.execute("mkpipe").unwrap();
+        kernel.execute("clone").unwrap();
+        let pipe = &kernel.state().pipes[&0];
+        assert_eq!((pipe.readers, pipe.writers), (2, 2));
+        // Parent keeps the read end, reading an empty pipe blocks
+        kernel.execute("close_write").unwrap();
+        kernel.execute("consume").unwrap();
+        assert_eq!(kernel.state().task(0).unwrap().status, TaskStatus::Blocked);
+        assert_eq!(kernel.state().cpus[0].current, Some(1));
+        // Writing wakes up the reader, which takes part of the data
+        kernel.execute("close_read").unwrap();
+        kernel.execute("fill").unwrap();
+        assert_eq!(kernel.state().task(0).unwrap().status, TaskStatus::Ready);
+        assert_eq!(kernel.state().pipes[&0].buffered, 1);
+        // A full pipe blocks the writer until the reader drains it
+        kernel.execute("produce").unwrap();
+        assert_eq!(kernel.state().task(1).unwrap().status, TaskStatus::Blocked);
+        assert_eq!(kernel.state().cpus[0].current, Some(0));
+        kernel.execute("drain").unwrap();
+        assert_eq!(kernel.state().task(1).unwrap().status, TaskStatus::Ready);
+        assert_eq!(kernel.state().pipes[&0].buffered, 2);
+        kernel.execute("drain").unwrap();
+        kernel.execute("drain").unwrap();
+        kernel.execute("consume").unwrap();
+        assert_eq!(kernel.state().task(0).unwrap().status, TaskStatus::Blocked);
+        // Exit of the last writer wakes the reader, which then reads end of file
+        kernel.execute("end").unwrap();
+        assert_eq!(kernel.state().pipes[&0].writers, 0);
+        assert_eq!(kernel.state().cpus[0].current, Some(0));
+        kernel.execute("drain").unwrap();
+        assert_eq!(kernel.state().task(0).unwrap().status, TaskStatus::Running);
+        kernel.execute("close_read").unwrap();
+        assert!(kernel.state().pipes.is_empty());
+    }
+
+    #[test]
+    fn signal_test() {
+        let mut kernel = kernel(
+            "event nap { block(io) sched }
+            event hit { kill(1, 2) }
+            event hit_self { kill(0, 2) }
+            event catch { sigaction(2, handle) }
+            event shield { sigmask([2]) }
+            event unshield { sigmask([]) }
+            event protect { sigaction(9, ignore) }
+            event yield { stop sched }
+            initial { tasks = [1, 0]; current = 1; }
+            kernel {
+                scheduler = fifo
+                events = [nap, hit, hit_self, catch, shield, unshield, protect, yield]
+            }",
+        );
+        // A signal interrupts a blocked task, which is terminated when it runs
+        kernel.execute("nap").unwrap();
+        kernel.execute("hit").unwrap();
+        assert!(kernel.state().wait_queues.is_empty());
+        assert_eq!(kernel.state().task(1).unwrap().status, TaskStatus::Ready);
+        kernel.execute("yield").unwrap();
+        assert!(kernel.state().task(1).is_none());
+        assert_eq!(kernel.state().cpus[0].current, Some(0));
+        // A handled signal is delivered once it is unmasked
+        kernel.execute("catch").unwrap();
+        kernel.execute("shield").unwrap();
+        kernel.execute("hit_self").unwrap();
+        assert!(kernel.state().task(0).unwrap().pending.contains(&2));
+        kernel.execute("unshield").unwrap();
+        assert!(kernel.state().task(0).unwrap().pending.is_empty());
+        assert_eq!(kernel.state().task(0).unwrap().status, TaskStatus::Running);
+        assert_eq!(kernel.execute("protect"), Err(Error::BadArgument));
+    }
+
+    #[test]
+    fn ipc_test() {
+        let mut kernel = kernel(
+            "event serve { recv(ep) sched }
+            event take { recv(ep) }
+            event answer { reply(7) }
+            event ask { call(ep, 3) sched }
+            event post { send(ep, 5) sched }
+            initial { tasks = [0, 1]; current = 0; }
+            kernel {
+                scheduler = fifo
+                events = [serve, take, answer, ask, post]
+            }",
+        );
+        // Server waits for a request, the client call hands it over and waits for the reply
+        kernel.execute("serve").unwrap();
+        assert_eq!(kernel.state().endpoints["ep"].receivers, vec![0]);
+        assert_eq!(kernel.state().cpus[0].current, Some(1));
+        kernel.execute("ask").unwrap();
+        assert!(kernel.state().endpoints.is_empty());
+        let server = kernel.state().task(0).unwrap();
+        assert_eq!(
+            server.message,
+            Some(Message {
+                sender: 1,
+                payload: 3
+            })
+        );
+        assert_eq!(server.reply_to, Some(1));
+        assert_eq!(kernel.state().cpus[0].current, Some(0));
+        assert_eq!(kernel.execute("take"), Err(Error::BadArgument));
+        kernel.execute("answer").unwrap();
+        let client = kernel.state().task(1).unwrap();
+        assert_eq!(
+            client.message,
+            Some(Message {
+                sender: 0,
+                payload: 7
+            })
+        );
+        assert_eq!(client.status, TaskStatus::Ready);
+        // A sender blocks until a receiver arrives
+        kernel.execute("post").unwrap();
+        assert_eq!(kernel.state().endpoints["ep"].senders, vec![(0, 5, false)]);
+        kernel.execute("take").unwrap();
+        assert!(kernel.state().endpoints.is_empty());
+        assert_eq!(kernel.state().task(0).unwrap().status, TaskStatus::Ready);
+        assert_eq!(kernel.execute("answer"), Err(Error::ObjectNotFound));
+    }
+
+    #[test]
+    fn limit_test() {
+        let mut kernel = kernel(
+            "event clone { fork }
+            event file { open(log) }
+            initial { tasks = [0]; current = 0; }
+            kernel {
+                scheduler = fifo
+                max_tasks = 1..2
+                max_fds = 1
+                events = [clone, file]
+            }",
+        );
+        kernel.execute("file").unwrap();
+        kernel.execute("file").unwrap();
+        assert_eq!(kernel.state().processes[&0].fds.len(), 1);
+        let task = kernel.state().task(0).unwrap();
+        assert_eq!(task.errno, Some(Errno::TooManyFiles));
+        // Forking a second task may either succeed or fail
+        let successors = kernel.successors("clone", 0);
+        assert_eq!(successors.len(), 2);
+        assert!(successors
+            .iter()
+            .any(|kernel| kernel.state().tasks.len() == 2
+                && kernel.state().task(0).unwrap().errno.is_none()));
+        assert!(successors
+            .iter()
+            .any(|kernel| kernel.state().tasks.len() == 1
+                && kernel.state().task(0).unwrap().errno == Some(Errno::Again)));
+        // Forking a third task always fails
+        let forked = successors
+            .iter()
+            .find(|kernel| kernel.state().tasks.len() == 2)
+            .unwrap();
+        let successors = forked.successors("clone", 0);
+        assert_eq!(successors.len(), 1);
+        assert_eq!(successors[0].state().tasks.len(), 2);
+        assert_eq!(
+            successors[0].state().task(0).unwrap().errno,
+            Some(Errno::Again)
+        );
+    }
+
+    #[test]
+    fn task_id_test() {
+        let mut kernel = kernel(
+            "event map { mmap(0, 1, rw) }
+            event touch { page_fault(0, w) }
+            event clone { fork }
+            event spawn { newtask }
+            event quit { exit sched }
+            initial { tasks = [0, 1]; current = 0; }
+            kernel {
+                scheduler = fifo
+                frames = 1
+                max_tasks = 3
+                events = [map, touch, clone, spawn, quit]
+            }",
+        );
+        // Forking needs a frame for every resident page
+        kernel.execute("map").unwrap();
+        kernel.execute("touch").unwrap();
+        kernel.execute("clone").unwrap();
+        assert_eq!(kernel.state().tasks.len(), 2);
+        assert_eq!(kernel.state().task(0).unwrap().errno, Some(Errno::NoMemory));
+        kernel.execute("spawn").unwrap();
+        assert_eq!(kernel.state().tasks.len(), 3);
+        kernel.execute("spawn").unwrap();
+        assert_eq!(kernel.state().task(0).unwrap().errno, Some(Errno::Again));
+        // Ids of reaped tasks are reused
+        kernel.execute("quit").unwrap();
+        kernel.execute("spawn").unwrap();
+        assert_eq!(kernel.state().task(0).unwrap().status, TaskStatus::Ready);
+        assert_eq!(kernel.state().tasks.len(), 3);
+    }
+    #[test]
+    fn interrupt_test() {
+        let mut kernel = kernel(
+            "interrupt event timer { irq_enter tick irq_exit }
+            event critical { irq_off }
+            event leave { irq_on }
+            event preempt { irq_enter stop sched irq_exit }
+            invariant no_atomic_sched at sched { irq_depth == 0 || irq_enabled }
+            initial { tasks = [0]; current = 0; }
+            kernel {
+                scheduler = fifo
+                events = [timer, critical, leave, preempt]
+            }",
+        );
+        // Interrupts are deferred while disabled
+        kernel.execute("critical").unwrap();
+        kernel.execute("timer").unwrap();
+        assert_eq!(kernel.state().time, 0);
+        assert!(kernel.state().cpus[0].pending_irqs.contains("timer"));
+        assert_eq!(kernel.execute("preempt"), Err(Error::InvariantViolated));
+        kernel.execute("leave").unwrap();
+        assert_eq!(kernel.state().time, 1);
+        assert!(kernel.state().cpus[0].pending_irqs.is_empty());
+        kernel.execute("preempt").unwrap();
+        assert_eq!(kernel.state().cpus[0].irq_depth, 0);
+    }
+    #[test]
+    fn stats_test() {
+        let mut kernel = kernel(
+            "event timer { tick }
+            event yield { stop sched }
+            invariant no_starvation { max_ready_time < 2 }
+            initial { tasks = [1, 0]; current = 0; }
+            kernel {
+                scheduler = fifo
+                events = [timer, yield]
+            }",
+        );
+        kernel.execute("timer").unwrap();
+        kernel.execute("yield").unwrap();
+        assert_eq!(kernel.state().context_switches, 1);
+        let task = kernel.state().task(1).unwrap();
+        assert_eq!(task.stats.scheduled, 1);
+        assert_eq!(task.stats.ready_time, 1);
+        let task = kernel.state().task(0).unwrap();
+        assert_eq!(task.stats.running_events, 2);
+        kernel.execute("timer").unwrap();
+        assert_eq!(kernel.state().task(0).unwrap().stats.ready_time, 1);
+        assert_eq!(kernel.execute("timer"), Err(Error::InvariantViolated));
+    }
+    #[test]
+    fn thread_test() {
+        let mut kernel = kernel(
+            "event spawn_thread { thread_create }
+            event quit_thread { thread_exit sched }
+            event quit_all { exit_group sched }
+            event end { exit }
+            event file { open(log) }
+            event clone { fork }
+            event join { wait sched }
+            initial { tasks = [0]; current = 0; init = 0; }
+            kernel {
+                scheduler = fifo
+                events = [spawn_thread, quit_thread, quit_all, end, file, clone, join]
+            }",
+        );
+        kernel.execute("file").unwrap();
+        kernel.execute("spawn_thread").unwrap();
+        kernel.execute("clone").unwrap();
+        assert_eq!(kernel.state().processes[&0].threads, vec![0, 1]);
+        assert_eq!(kernel.state().task(2).unwrap().parent, Some(0));
+        // The first thread exits, the process lives on in the other thread
+        kernel.execute("quit_thread").unwrap();
+        assert_eq!(kernel.state().task(0).unwrap().status, TaskStatus::Zombie);
+        assert_eq!(kernel.state().processes[&0].threads, vec![1]);
+        assert_eq!(kernel.state().cpus[0].current, Some(1));
+        // Threads share the descriptor table and the children of the process
+        kernel.execute("file").unwrap();
+        assert_eq!(kernel.state().processes[&0].fds.len(), 2);
+        kernel.execute("join").unwrap();
+        kernel.execute("quit_all").unwrap();
+        assert!(kernel.state().task(2).is_none());
+        assert_eq!(kernel.state().cpus[0].current, Some(1));
+        // The last thread exiting tears down the process
+        kernel.execute("end").unwrap();
+        assert!(kernel.state().tasks.is_empty());
+        assert!(kernel.state().processes.is_empty());
+    }
+    #[test]
+    fn bad_state_test() {
+        let kml = "event timer { tick }
+            initial { tasks = [0]; current = 0; }
+            kernel { scheduler = mlfq([1, 2]) events = [timer] }";
+        let restore = |change: &dyn Fn(&mut TaskControlBlock)| {
+            let mut state = kernel(kml).state().clone();
+            change(&mut state.tasks[0]);
+            let model = lex_and_parse_kml(kml).unwrap();
+            Kernel::from_model(model, Some(state), &Registry::default()).map(|_| ())
+        };
+        assert_eq!(restore(&|task| task.nice = 19), Ok(()));
+        assert_eq!(restore(&|task| task.nice = 20), Err(Error::BadInitialState));
+        // Every level must have a queue
+        assert_eq!(restore(&|task| task.level = 1), Ok(()));
+        assert_eq!(restore(&|task| task.level = 2), Err(Error::BadInitialState));
+    }
+    #[test]
+    fn exit_group_test() {
+        let mut kernel = kernel(
+            "event spawn_thread { thread_create }
+            event nap { block(io) sched }
+            event quit_all { exit_group sched }
+            initial { tasks = [0]; current = 0; }
+            kernel {
+                scheduler = fifo
+                on_empty = idle
+                events = [spawn_thread, nap, quit_all]
+            }",
+        );
+        kernel.execute("spawn_thread").unwrap();
+        kernel.execute("spawn_thread").unwrap();
+        kernel.execute("nap").unwrap();
+        assert_eq!(kernel.state().task(0).unwrap().status, TaskStatus::Blocked);
+        assert_eq!(kernel.state().cpus[0].current, Some(1));
+        assert_eq!(kernel.state().task(2).unwrap().status, TaskStatus::Ready);
+        // Blocked and ready threads go down with the one calling exit_group
+        kernel.execute("quit_all").unwrap();
+        assert!(kernel.state().tasks.is_empty());
+        assert!(kernel.state().processes.is_empty());
+        assert!(kernel.state().wait_queues.is_empty());
+        assert!(kernel.state().ready_queue.is_empty());
+        assert_eq!(kernel.state().cpus[0].current, None);
+    }
+    #[test]
+    fn on_empty_test() {
+        let with_policy = |policy: &str| {
+            kernel(&format!(
+                "event nap {{ block(io) sched }}
+                event spin {{ sched }}
+                initial {{ tasks = [0]; current = 0; }}
+                kernel {{
+                    scheduler = fifo
+                    on_empty = {}
+                    events = [nap, spin]
+                }}",
+                policy
+            ))
+        };
+        // Without a policy the kernel shuts down
+        let mut kernel = kernel(
+            "event nap { block(io) sched }
+            initial { tasks = [0]; current = 0; }
+            kernel { scheduler = fifo events = [nap] }",
+        );
+        kernel.execute("nap").unwrap();
+        assert_eq!(kernel.shutdown_code(), Some(0));
+        let mut kernel = with_policy("error");
+        assert_eq!(kernel.execute("nap"), Err(Error::NoReadyTask));
+        let mut kernel = with_policy("shutdown(3)");
+        kernel.execute("nap").unwrap();
+        assert_eq!(kernel.shutdown_code(), Some(3));
+        // A running task keeps the cpu, a blocked one leaves it idle
+        let mut kernel = with_policy("idle");
+        kernel.execute("spin").unwrap();
+        assert_eq!(kernel.state().cpus[0].current, Some(0));
+        kernel.execute("nap").unwrap();
+        assert_eq!(kernel.state().cpus[0].current, None);
+        assert_eq!(kernel.execute("spin"), Ok(()));
+    }
+}
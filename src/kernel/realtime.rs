@@ -0,0 +1,103 @@
+use super::Kernel;
+use crate::error::Error;
+use crate::state::*;
+
+impl Kernel {
+    /// Make current task a real-time task, its first job is released at once. The
+    /// deadline is relative to the release and defaults to the period. A sporadic
+    /// task waits for `release` between jobs, at least `period` ticks apart.
+    pub(super) fn real_time(
+        &mut self,
+        sporadic: bool,
+        period: u32,
+        wcet: u32,
+        deadline: Option<u32>,
+    ) -> Result<(), Error> {
+        let deadline = deadline.unwrap_or(period);
+        if wcet == 0 || wcet > deadline || period == 0 {
+            return Err(Error::BadArgument);
+        }
+        let cur = self.current()?;
+        let time = self.state.time;
+        self.state.task_mut(cur).ok_or(Error::TaskNotFound)?.rt = Some(RealTime {
+            sporadic,
+            period,
+            wcet,
+            deadline,
+            release: time,
+            executed: 0,
+            done: false,
+            missed: false,
+            misses: 0,
+            overrun: false,
+            overruns: 0,
+        });
+        Ok(())
+    }
+    /// Complete the job of current task. A periodic task blocks until its next release
+    /// unless that is already due, a sporadic task until `release`.
+    pub(super) fn job_done(&mut self) -> Result<(), Error> {
+        let cur = self.current()?;
+        let time = self.state.time;
+        let rt = self
+            .state
+            .task_mut(cur)
+            .ok_or(Error::TaskNotFound)?
+            .rt
+            .as_mut()
+            .ok_or(Error::BadArgument)?;
+        let next = rt.release + rt.period as u64;
+        if !rt.sporadic && next <= time {
+            rt.start_job(next);
+            return Ok(());
+        }
+        rt.done = true;
+        let sporadic = rt.sporadic;
+        self.suspend()?;
+        if !sporadic {
+            self.state.task_mut(cur).unwrap().wake_at = Some(next);
+        }
+        Ok(())
+    }
+    /// Release the next job of a sporadic task, which fails if it arrives sooner
+    /// than its period after the last one
+    pub(super) fn release(&mut self, id: TaskId) -> Result<(), Error> {
+        let time = self.state.time;
+        let task = self.state.task_mut(id).ok_or(Error::TaskNotFound)?;
+        let rt = task.rt.as_mut().ok_or(Error::BadArgument)?;
+        if !rt.sporadic || !rt.done || task.status != TaskStatus::Blocked {
+            return Err(Error::BadArgument);
+        }
+        if time < rt.release + rt.period as u64 {
+            return Err(Error::BadArgument);
+        }
+        rt.start_job(time);
+        self.wakeup(id)
+    }
+    /// Account a tick to the jobs of real-time tasks, a job misses its deadline if it
+    /// has not completed by then and overruns if it runs longer than its worst-case
+    /// execution time
+    pub(super) fn account_jobs(&mut self) {
+        let time = self.state.time;
+        for task in self.state.tasks.iter_mut() {
+            let running = task.status == TaskStatus::Running;
+            if task.status == TaskStatus::Zombie {
+                continue;
+            }
+            let Some(rt) = task.rt.as_mut().filter(|rt| !rt.done) else {
+                continue;
+            };
+            if running {
+                rt.executed += 1;
+            }
+            if !rt.missed && time > rt.absolute_deadline() {
+                rt.missed = true;
+                rt.misses += 1;
+            }
+            if !rt.overrun && rt.executed > rt.wcet {
+                rt.overrun = true;
+                rt.overruns += 1;
+            }
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use super::Kernel;
+use crate::error::Error;
+use crate::state::*;
+
+impl Kernel {
+    /// Send a signal to a task. A blocked task the signal is not masked for is woken
+    /// up, abandoning whatever it waits for, so that the signal can be delivered.
+    pub(super) fn kill(&mut self, id: TaskId, sig: Signal) -> Result<(), Error> {
+        check_signal(sig)?;
+        let task = self
+            .state
+            .task_mut(id)
+            .filter(|task| task.status != TaskStatus::Zombie)
+            .ok_or(Error::TaskNotFound)?;
+        if task.sigaction(sig) == SigAction::Ignore {
+            return Ok(());
+        }
+        task.pending.insert(sig);
+        if task.status == TaskStatus::Blocked && !task.sigmask.contains(&sig) {
+            self.dequeue(id);
+            self.wakeup(id)?;
+        }
+        Ok(())
+    }
+    /// Replace the signal mask of current task
+    pub(super) fn sigmask(&mut self, sigs: &[Signal]) -> Result<(), Error> {
+        for &sig in sigs {
+            check_signal(sig)?;
+            if sig == SIGKILL {
+                return Err(Error::BadArgument);
+            }
+        }
+        let cur = self.current()?;
+        self.state.task_mut(cur).ok_or(Error::TaskNotFound)?.sigmask =
+            sigs.iter().copied().collect();
+        Ok(())
+    }
+    /// Set the disposition of a signal for current task, ignoring a signal discards
+    /// it if pending
+    pub(super) fn sigaction(&mut self, sig: Signal, action: SigAction) -> Result<(), Error> {
+        check_signal(sig)?;
+        if sig == SIGKILL {
+            return Err(Error::BadArgument);
+        }
+        let cur = self.current()?;
+        let task = self.state.task_mut(cur).ok_or(Error::TaskNotFound)?;
+        match action {
+            SigAction::Default => task.sigactions.remove(&sig),
+            _ => task.sigactions.insert(sig, action),
+        };
+        if action == SigAction::Ignore {
+            task.pending.remove(&sig);
+        }
+        Ok(())
+    }
+    /// Deliver unmasked pending signals to the task returning to user mode on the
+    /// cpu, lowest signal first. A task terminated by a signal exits and the cpu is
+    /// rescheduled, the next task then gets its signals delivered.
+    pub(super) fn deliver_signals(&mut self) -> Result<(), Error> {
+        while self.shutdown_code.is_none() {
+            let Ok(cur) = self.current() else {
+                return Ok(());
+            };
+            let task = self.state.task_mut(cur).ok_or(Error::TaskNotFound)?;
+            if task.status != TaskStatus::Running {
+                return Ok(());
+            }
+            let Some(sig) = task
+                .pending
+                .iter()
+                .copied()
+                .find(|sig| !task.sigmask.contains(sig))
+            else {
+                return Ok(());
+            };
+            task.pending.remove(&sig);
+            if task.sigaction(sig) == SigAction::Default {
+                self.exit_task(cur)?;
+                self.sched()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn check_signal(sig: Signal) -> Result<(), Error> {
+    if (1..NSIG).contains(&sig) {
+        Ok(())
+    } else {
+        Err(Error::BadArgument)
+    }
+}
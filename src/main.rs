@@ -1,16 +1,16 @@
+mod check;
 mod error;
 mod event;
+mod explore;
 mod kernel;
 mod parse;
 mod scheduler;
+mod session;
 mod state;
 
 use clap::Parser;
-use error::Error;
-use event::*;
 use kernel::*;
-use parse::*;
-use scheduler::*;
+use session::Session;
 use std::{fs::OpenOptions, io::Write};
 
 #[derive(Parser, Debug)]
@@ -19,6 +19,29 @@ struct Args {
     /// Path of the kml file
     #[arg(short, long)]
     file: String,
+    /// Path of a json file describing the initial state, overrides the `initial` block
+    #[arg(long)]
+    initial_state: Option<String>,
+    /// Explore all states reachable within the given number of events instead of
+    /// running interactively
+    #[arg(long)]
+    explore: Option<usize>,
+    /// Path of a json trace of a real kernel to check against the model instead of
+    /// running interactively
+    #[arg(long)]
+    check: Option<String>,
+    /// Print states as json instead of text
+    #[arg(long)]
+    json: bool,
+    /// Seed of the random choices, overrides the `seed` setting
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Path of a json file to record the seed and events of the interactive session to
+    #[arg(long)]
+    record: Option<String>,
+    /// Path of a recorded session to replay before running interactively
+    #[arg(long, conflicts_with = "seed")]
+    replay: Option<String>,
 }
 
 fn main() {
@@ -32,54 +55,117 @@ fn main() {
     // parse
     let model = parse::lex_and_parse_kml(&kml).expect("Failed to parse kml");
 
+    // Initial state given in json overrides the one in kml
+    let initial_state = args.initial_state.map(|path| {
+        let json_file = OpenOptions::new()
+            .read(true)
+            .open(path)
+            .expect("Failed to open initial state file");
+        serde_json::from_reader(json_file).expect("Failed to parse initial state")
+    });
+
     // build model
-    // def events
-    let mut events = Vec::new();
-    for def in model.event_defs {
-        events.push(Event::new(def));
+    // Schedulers written in Rust are registered here under the name used in kml
+    let registry = scheduler::Registry::default();
+    let mut kernel =
+        Kernel::from_model(model, initial_state, &registry).expect("Failed to build kernel");
+    if let Some(seed) = args.seed {
+        kernel.set_seed(seed);
     }
-    // Config kernel
-    let mut enabled_events = None;
-    let mut scheduler = None;
-    for config in &model.kernel_def.configs {
-        if let KernelConfig::Events(names) = config {
-            enabled_events = Some(
-                events
-                    .into_iter()
-                    .filter(|event| names.contains(&Identifier(event.name().to_owned())))
-                    .collect::<Vec<_>>(),
-            );
-            break;
+
+    // A replayed session restarts from its own seed
+    let replay = args.replay.map(|path| {
+        let session_file = OpenOptions::new()
+            .read(true)
+            .open(path)
+            .expect("Failed to open session file");
+        let session: Session =
+            serde_json::from_reader(session_file).expect("Failed to parse session");
+        kernel.set_seed(session.seed);
+        session.events
+    });
+    let mut replay = replay.unwrap_or_default().into_iter();
+    println!("Kernel Model Created!");
+    kernel.print_config();
+
+    if let Some(depth) = args.explore {
+        for (depth, found) in explore::explore(&kernel, depth).iter().enumerate() {
+            if found.missed > 0 {
+                println!(
+                    "Depth {}: {} new states, {} past a deadline",
+                    depth, found.states, found.missed
+                );
+            } else {
+                println!("Depth {}: {} new states", depth, found.states);
+            }
+            for path in &found.violations {
+                println!("Invariant violated after [{}]", path.join(", "));
+            }
         }
+        return;
     }
-    for config in &model.kernel_def.configs {
-        if let KernelConfig::Scheduler(type_) = config {
-            scheduler = Some(Scheduler::new(*type_));
-            break;
+
+    if let Some(path) = args.check {
+        let trace_file = OpenOptions::new()
+            .read(true)
+            .open(path)
+            .expect("Failed to open trace file");
+        let trace: Vec<check::Step> =
+            serde_json::from_reader(trace_file).expect("Failed to parse trace");
+        match check::check(&kernel, &trace) {
+            Ok(()) => println!("Trace conforms to the model"),
+            Err(step) => {
+                println!("Step {} does not conform to the model", step);
+                std::process::exit(1);
+            }
         }
+        return;
     }
-    // Verification in parser ensures events and scheduler are not empty
-    let mut kernel = Kernel::new(enabled_events.unwrap(), scheduler.unwrap());
-    println!("Kernel Model Created!");
-    kernel.print_config();
 
     // Run
+    let mut session = Session {
+        seed: kernel.seed(),
+        events: Vec::new(),
+    };
     while kernel.shutdown_code().is_none() {
-        kernel.print_state();
+        if args.json {
+            kernel.print_state_json();
+        } else {
+            kernel.print_state();
+        }
         print!("event>> ");
         std::io::stdout().flush().unwrap();
-        let mut event = String::new();
-        std::io::stdin().read_line(&mut event).unwrap();
-        if let Err(e) = kernel.execute(event.trim()) {
-            println!("Error: {:?}", e);
-            if e == Error::NoReadyTask {
-                kernel.shutdown(0).unwrap();
+        let event = match replay.next() {
+            Some(event) => {
+                println!("{}", event);
+                event
             }
+            None => {
+                let mut event = String::new();
+                if std::io::stdin().read_line(&mut event).unwrap() == 0 {
+                    println!();
+                    break;
+                }
+                event.trim().to_string()
+            }
+        };
+        let misses = kernel.state().deadline_misses();
+        match kernel.execute(&event) {
+            Ok(()) => {
+                session.events.push(event);
+                if let Some(path) = &args.record {
+                    let json = serde_json::to_string_pretty(&session).unwrap();
+                    std::fs::write(path, json).expect("Failed to write session file");
+                }
+            }
+            Err(e) => println!("Error: {:?}", e),
+        }
+        if kernel.state().deadline_misses() > misses {
+            println!("Deadline missed");
         }
     }
 
-    println!(
-        "Kernel shutdown with code: {}",
-        kernel.shutdown_code().unwrap()
-    );
+    if let Some(code) = kernel.shutdown_code() {
+        println!("Kernel shutdown with code: {}", code);
+    }
 }
@@ -1,25 +1,227 @@
-use super::lexer::TokenKind;
-
 // Program
 #[derive(Debug)]
 pub struct Model {
     pub event_defs: Vec<EventDef>,
+    pub invariant_defs: Vec<InvariantDef>,
+    pub policy_defs: Vec<PolicyDef>,
+    pub initial_def: Option<InitialDef>,
     pub kernel_def: KernelDef,
 }
 
 #[derive(Debug)]
 pub struct EventDef {
     pub name: Identifier,
+    /// Interrupt events are deferred while interrupts are disabled
+    pub interrupt: bool,
     pub body: Vec<EventAction>,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub enum EventAction {
     Shutdown,
-    NewTask,
+    /// Create a task, optionally with a priority
+    NewTask(Option<i32>),
     Exit,
     Sched,
     Stop,
+    Block(Identifier),
+    WakeupOne(Identifier),
+    WakeupAll(Identifier),
+    Fork,
+    Wait,
+    SetPrio(i32),
+    SetNice(i32),
+    /// Set the share of current task under proportional-share schedulers
+    SetTickets(u32),
+    /// Create a semaphore with an initial count
+    SemCreate(Identifier, u32),
+    SemDown(Identifier),
+    SemUp(Identifier),
+    MutexLock(Identifier),
+    MutexUnlock(Identifier),
+    /// Advance the logical clock by one
+    Tick,
+    /// Block current task for a number of ticks
+    Sleep(u32),
+    /// Map pages `start..start + pages`
+    Mmap(u32, u32, Permission),
+    /// Unmap pages `start..start + pages`
+    Munmap(u32, u32),
+    /// Access a page, the permission has only the accessed bit set
+    PageFault(u32, Permission),
+    /// Set heap size in pages
+    Brk(u32),
+    /// Open a named file on the lowest free descriptor
+    Open(Identifier),
+    Close(u32),
+    Dup(u32),
+    /// Create a pipe, optionally with a buffer capacity
+    Pipe(Option<u32>),
+    /// Read or write an amount of data through a descriptor
+    Read(u32, u32),
+    Write(u32, u32),
+    /// Send a signal to a task
+    Kill(u32, Signal),
+    /// Replace the blocked signal set of current task
+    SigMask(Vec<Signal>),
+    SigAction(Signal, SigAction),
+    /// Send a payload through an endpoint
+    Send(Identifier, u32),
+    Recv(Identifier),
+    /// Send a payload and wait for the reply
+    Call(Identifier, u32),
+    /// Answer the last call received by current task
+    Reply(u32),
+    /// Disable interrupts on the cpu
+    IrqOff,
+    /// Enable interrupts on the cpu, running interrupt events deferred meanwhile
+    IrqOn,
+    /// Enter or leave a (possibly nested) interrupt handler
+    IrqEnter,
+    IrqExit,
+    /// Add a thread to the process of current task
+    ThreadCreate,
+    /// Exit current thread only
+    ThreadExit,
+    /// Terminate every thread of the process of current task, whatever their state
+    ExitGroup,
+    /// Stop current task and reschedule once its time slice is used up
+    Preempt,
+    /// Make current task real-time with a period, a worst-case execution time and
+    /// optionally a relative deadline
+    Periodic(u32, u32, Option<u32>),
+    Sporadic(u32, u32, Option<u32>),
+    /// Complete the job of current task
+    JobDone,
+    /// Release the next job of a sporadic task
+    Release(u32),
+}
+
+impl EventAction {
+    /// Keyword of the action, used to name it in invariants
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            EventAction::Shutdown => "shutdown",
+            EventAction::NewTask(_) => "newtask",
+            EventAction::Exit => "exit",
+            EventAction::Sched => "sched",
+            EventAction::Stop => "stop",
+            EventAction::Block(_) => "block",
+            EventAction::WakeupOne(_) => "wakeup_one",
+            EventAction::WakeupAll(_) => "wakeup_all",
+            EventAction::Fork => "fork",
+            EventAction::Wait => "wait",
+            EventAction::SetPrio(_) => "setprio",
+            EventAction::SetNice(_) => "setnice",
+            EventAction::SetTickets(_) => "settickets",
+            EventAction::SemCreate(..) => "sem_create",
+            EventAction::SemDown(_) => "sem_down",
+            EventAction::SemUp(_) => "sem_up",
+            EventAction::MutexLock(_) => "mutex_lock",
+            EventAction::MutexUnlock(_) => "mutex_unlock",
+            EventAction::Tick => "tick",
+            EventAction::Sleep(_) => "sleep",
+            EventAction::Mmap(..) => "mmap",
+            EventAction::Munmap(..) => "munmap",
+            EventAction::PageFault(..) => "page_fault",
+            EventAction::Brk(_) => "brk",
+            EventAction::Open(_) => "open",
+            EventAction::Close(_) => "close",
+            EventAction::Dup(_) => "dup",
+            EventAction::Pipe(_) => "pipe",
+            EventAction::Read(..) => "read",
+            EventAction::Write(..) => "write",
+            EventAction::Kill(..) => "kill",
+            EventAction::SigMask(_) => "sigmask",
+            EventAction::SigAction(..) => "sigaction",
+            EventAction::Send(..) => "send",
+            EventAction::Recv(_) => "recv",
+            EventAction::Call(..) => "call",
+            EventAction::Reply(_) => "reply",
+            EventAction::IrqOff => "irq_off",
+            EventAction::IrqOn => "irq_on",
+            EventAction::IrqEnter => "irq_enter",
+            EventAction::IrqExit => "irq_exit",
+            EventAction::ThreadCreate => "thread_create",
+            EventAction::ThreadExit => "thread_exit",
+            EventAction::ExitGroup => "exit_group",
+            EventAction::Preempt => "preempt",
+            EventAction::Periodic(..) => "periodic",
+            EventAction::Sporadic(..) => "sporadic",
+            EventAction::JobDone => "job_done",
+            EventAction::Release(_) => "release",
+        }
+    }
+}
+
+/// A condition that must hold after every event, or right before every action
+/// with the given keyword
+#[derive(Debug, Clone)]
+pub struct InvariantDef {
+    pub name: Identifier,
+    pub at: Option<String>,
+    pub expr: Expr,
+}
+
+/// Integer expression over the state seen from the cpu an event runs on,
+/// booleans are 0 and 1
+#[derive(Debug, Clone)]
+pub enum Expr {
+    Number(u32),
+    Variable(String),
+    Not(Box<Expr>),
+    Binary(Box<Expr>, BinaryOp, Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum BinaryOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+}
+
+/// Variables an expression may refer to
+pub const VARIABLES: &[&str] = &[
+    "irq_enabled",
+    "irq_depth",
+    "time",
+    "tasks",
+    "ready",
+    "blocked",
+    "idle",
+    "context_switches",
+    "max_ready_time",
+    "min_scheduled",
+    "max_scheduled",
+    "deadline_misses",
+    "wcet_overruns",
+];
+
+#[derive(Debug)]
+pub struct InitialDef {
+    pub configs: Vec<InitialConfig>,
+}
+
+#[derive(Debug)]
+pub enum InitialConfig {
+    Tasks(Vec<u32>),
+    /// Tasks running on each cpu, in cpu order
+    Current(Vec<u32>),
+    Init(u32),
+    Time(u32),
+    Priority(u32, i32),
+    Nice(u32, i32),
+    /// Tasks blocked on a wait queue, in wakeup order
+    Blocked(Identifier, Vec<u32>),
+    /// Semaphore with its count
+    Semaphore(Identifier, u32),
+    /// Mutex with its owner
+    Mutex(Identifier, u32),
 }
 
 #[derive(Debug)]
@@ -31,27 +233,145 @@ pub struct KernelDef {
 pub enum KernelConfig {
     Events(Vec<Identifier>),
     Scheduler(SchedulerType),
+    Cpus(u32),
+    /// Number of physical frames, enables the memory model
+    Frames(u32),
+    Limit(Resource, Limit),
+    /// What `sched` does when no task is ready
+    OnEmpty(OnEmpty),
+    /// Ticks a task runs before `preempt` switches it out
+    TimeSlice(u32),
+    /// Seed of the random choices made when simulating
+    Seed(u64),
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OnEmpty {
+    /// Keep running current task if it can, otherwise the cpu runs its idle task
+    Idle,
+    /// Shut the kernel down with an exit code, `shutdown(0)` unless set otherwise
+    Shutdown(i32),
+    /// Fail the event with `Error::NoReadyTask`
+    Error,
+}
+
+impl Default for OnEmpty {
+    fn default() -> Self {
+        OnEmpty::Shutdown(0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Resource {
+    /// Tasks in the system, including zombies
+    Tasks,
+    /// Open descriptors of a task
+    Fds,
+    /// Resident pages of a task
+    Frames,
+}
+
+/// Limit on a resource written as `max` or `min..max`. Up to `min` units always
+/// succeed, beyond `max` units always fail, and in between both outcomes are permitted.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limit {
+    pub min: u32,
+    pub max: u32,
+}
+
+#[derive(Debug, Clone)]
 pub enum SchedulerType {
     Fifo,
     Random,
+    /// Round robin through the ready queue
+    Rr,
+    /// Highest priority first, preempting a running task of lower priority
+    Priority(TieBreak),
+    /// Multi-level feedback queue with the quantum of each level, highest level
+    /// first, and the period in ticks of boosting all tasks to the highest level
+    Mlfq {
+        quanta: Vec<u32>,
+        boost: Option<u32>,
+    },
+    /// Smallest virtual runtime first, running time is weighted by nice value
+    Cfs,
+    /// Earliest absolute deadline first among real-time tasks, then other tasks
+    Edf,
+    /// Rate monotonic, shortest period first among real-time tasks, then other tasks
+    Rm,
+    /// Draw a ready task with a chance proportional to its tickets
+    Lottery,
+    /// Smallest pass first, the pass of a running task advances inversely to its tickets
+    Stride,
+    /// A scheduler declared in kml or registered under this name
+    Custom(String),
 }
 
-impl From<TokenKind> for EventAction {
-    fn from(token: TokenKind) -> Self {
-        match token {
-            TokenKind::Shutdown => EventAction::Shutdown,
-            TokenKind::NewTask => EventAction::NewTask,
-            TokenKind::Exit => EventAction::Exit,
-            TokenKind::Sched => EventAction::Sched,
-            TokenKind::Stop => EventAction::Stop,
-            _ => panic!("Invalid event action"),
+impl SchedulerType {
+    /// Name the scheduler is registered under
+    pub fn name(&self) -> &str {
+        match self {
+            SchedulerType::Fifo => "fifo",
+            SchedulerType::Random => "random",
+            SchedulerType::Rr => "rr",
+            SchedulerType::Priority(_) => "priority",
+            SchedulerType::Mlfq { .. } => "mlfq",
+            SchedulerType::Cfs => "cfs",
+            SchedulerType::Edf => "edf",
+            SchedulerType::Rm => "rm",
+            SchedulerType::Lottery => "lottery",
+            SchedulerType::Stride => "stride",
+            SchedulerType::Custom(name) => name,
         }
     }
 }
 
+/// Scheduler declared in kml, picking the ready tasks that come first in the order
+/// given by its keys. Tasks tied on every key are all legal choices.
+#[derive(Debug, Clone)]
+pub struct PolicyDef {
+    pub name: Identifier,
+    pub keys: Vec<SortKey>,
+    /// A running task gives way to a ready task that comes strictly before it
+    pub preemptive: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortKey {
+    Min(TaskField),
+    Max(TaskField),
+}
+
+/// Property of a task a kml scheduler orders by
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TaskField {
+    Id,
+    Priority,
+    Nice,
+    Tickets,
+    /// Absolute deadline of the current job, after every other task if not real-time
+    Deadline,
+    /// Period of a real-time task, after every other task if not real-time
+    Period,
+    /// Position in the ready queue
+    Queue,
+    /// Ticks spent ready
+    ReadyTime,
+    /// Times picked by the scheduler
+    Scheduled,
+}
+
+/// Order among ready tasks of the same priority
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum TieBreak {
+    /// Ready queue order
+    #[default]
+    Fifo,
+    LowestId,
+}
+
+pub use crate::state::{Permission, SigAction, Signal};
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Identifier(pub String);
 
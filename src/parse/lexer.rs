@@ -193,11 +193,14 @@ pub enum TokenKind {
     #[regex(r"#[^\n\f]*", logos::skip)]
     Comment,
 
-    // Identifier
+    // Identifier, which also carries contextual words such as most action and config names
 
     #[regex(r#"[_a-zA-Z][_a-zA-Z0-9]*"#)]
     Identifier,
-    
+
+    #[regex(r"[0-9]+")]
+    Number,
+
     // Event defs
 
     #[token("event", ignore(ascii_case))]
@@ -236,9 +239,6 @@ pub enum TokenKind {
     #[token("fifo", ignore(ascii_case))]
     Fifo,
 
-    #[token("random", ignore(ascii_case))]
-    Random,
-
     // Other markers
 
     #[token("{")]
@@ -247,6 +247,12 @@ pub enum TokenKind {
     #[token("}")]
     RBrace,
 
+    #[token("(")]
+    LParen,
+
+    #[token(")")]
+    RParen,
+
     #[token("[")]
     LBracket,
 
@@ -256,19 +262,57 @@ pub enum TokenKind {
     #[token(",")]
     Comma,
 
+    #[token(";")]
+    Semicolon,
+
+    #[token("-")]
+    Minus,
+
+    #[token("..")]
+    DotDot,
+
     #[token("=")]
     Eq,
+
+    // Operators
+
+    #[token("==")]
+    EqEq,
+
+    #[token("!=")]
+    Ne,
+
+    #[token("<")]
+    Lt,
+
+    #[token("<=")]
+    Le,
+
+    #[token(">")]
+    Gt,
+
+    #[token(">=")]
+    Ge,
+
+    #[token("&&")]
+    And,
+
+    #[token("||")]
+    Or,
+
+    #[token("!")]
+    Not,
 }
 
 impl TokenKind {
     pub fn is_action(&self) -> bool {
-        match *self {
-            Self::Sched | Self::Stop | Self::Shutdown | Self::Exit | Self::NewTask => true,
-            _ => false,
-        }
+        matches!(
+            *self,
+            Self::Sched | Self::Stop | Self::Shutdown | Self::Exit | Self::NewTask
+        )
     }
 }
 
-pub fn tokenize_kml(kml: &str) -> Result<Vec<Token>, Error> {
+pub fn tokenize_kml(kml: &str) -> Result<Vec<Token<'_>>, Error> {
     Tokenizer::new(kml).collect::<Result<Vec<_>, _>>()
 }
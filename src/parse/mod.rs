@@ -21,6 +21,13 @@ fn verify(model: &Model) -> Result<(), Error> {
     // Events config and scheduler config must be present and only present once
     let mut events_config = None;
     let mut scheduler_config = None;
+    // Other configs are optional but may only be present once
+    let mut cpus_config = None;
+    let mut frames_config = None;
+    let mut limits = Vec::new();
+    let mut on_empty_config = None;
+    let mut time_slice_config = None;
+    let mut seed_config = None;
     for config in &model.kernel_def.configs {
         match config {
             KernelConfig::Events(event) => {
@@ -33,8 +40,49 @@ fn verify(model: &Model) -> Result<(), Error> {
                 if scheduler_config.is_some() {
                     return Err(Error::BadKernelConfig);
                 }
+                if let SchedulerType::Mlfq { quanta, boost } = scheduler {
+                    if quanta.is_empty() || quanta.contains(&0) || *boost == Some(0) {
+                        return Err(Error::BadKernelConfig);
+                    }
+                }
                 scheduler_config = Some(scheduler);
             }
+            KernelConfig::Cpus(cpus) => {
+                if cpus_config.is_some() || *cpus == 0 {
+                    return Err(Error::BadKernelConfig);
+                }
+                cpus_config = Some(*cpus);
+            }
+            KernelConfig::Frames(frames) => {
+                if frames_config.is_some() {
+                    return Err(Error::BadKernelConfig);
+                }
+                frames_config = Some(*frames);
+            }
+            KernelConfig::Limit(resource, limit) => {
+                if limits.contains(resource) || limit.min > limit.max {
+                    return Err(Error::BadKernelConfig);
+                }
+                limits.push(*resource);
+            }
+            KernelConfig::OnEmpty(on_empty) => {
+                if on_empty_config.is_some() {
+                    return Err(Error::BadKernelConfig);
+                }
+                on_empty_config = Some(*on_empty);
+            }
+            KernelConfig::TimeSlice(ticks) => {
+                if time_slice_config.is_some() || *ticks == 0 {
+                    return Err(Error::BadKernelConfig);
+                }
+                time_slice_config = Some(*ticks);
+            }
+            KernelConfig::Seed(seed) => {
+                if seed_config.is_some() {
+                    return Err(Error::BadKernelConfig);
+                }
+                seed_config = Some(*seed);
+            }
         }
     }
     if events_config.is_none() || scheduler_config.is_none() {
@@ -42,10 +90,124 @@ fn verify(model: &Model) -> Result<(), Error> {
     }
     // Check if events are defined
     for event in events_config.unwrap() {
-        if model.event_defs.iter().find(|e| e.name == *event).is_none() {
+        if !model.event_defs.iter().any(|e| e.name == *event) {
+            return Err(Error::BadKernelConfig);
+        }
+    }
+    // Schedulers declared in kml must have distinct names
+    for (i, policy) in model.policy_defs.iter().enumerate() {
+        if model.policy_defs[..i].iter().any(|p| p.name == policy.name) {
+            return Err(Error::BadKernelConfig);
+        }
+    }
+    // Invariants may only refer to known variables
+    for invariant in &model.invariant_defs {
+        if !uses_known_variables(&invariant.expr) {
             return Err(Error::BadKernelConfig);
         }
     }
+    if let Some(initial) = &model.initial_def {
+        verify_initial(initial, cpus_config.unwrap_or(1))?;
+    }
+    Ok(())
+}
+
+fn uses_known_variables(expr: &Expr) -> bool {
+    match expr {
+        Expr::Number(_) => true,
+        Expr::Variable(name) => VARIABLES.contains(&name.as_str()),
+        Expr::Not(expr) => uses_known_variables(expr),
+        Expr::Binary(lhs, _, rhs) => uses_known_variables(lhs) && uses_known_variables(rhs),
+    }
+}
+
+fn verify_initial(initial: &InitialDef, cpus: u32) -> Result<(), Error> {
+    // Tasks config and current config must be present and only present once
+    let mut tasks_config = None;
+    let mut current_config = None;
+    let mut init_config = None;
+    let mut time_config = None;
+    // Variables set per task, and per named object, may only be set once
+    let mut task_vars = Vec::new();
+    let mut objects = Vec::new();
+    let mut blocked = Vec::new();
+    for config in &initial.configs {
+        match config {
+            InitialConfig::Tasks(tasks) => {
+                if tasks_config.is_some() {
+                    return Err(Error::BadInitialState);
+                }
+                tasks_config = Some(tasks);
+            }
+            InitialConfig::Current(current) => {
+                if current_config.is_some() {
+                    return Err(Error::BadInitialState);
+                }
+                current_config = Some(current);
+            }
+            InitialConfig::Init(init) => {
+                if init_config.is_some() {
+                    return Err(Error::BadInitialState);
+                }
+                init_config = Some(init);
+            }
+            InitialConfig::Time(time) => {
+                if time_config.is_some() {
+                    return Err(Error::BadInitialState);
+                }
+                time_config = Some(time);
+            }
+            InitialConfig::Priority(id, _) => task_vars.push(("priority", *id)),
+            InitialConfig::Nice(id, nice) => {
+                if !(-20..=19).contains(nice) {
+                    return Err(Error::BadInitialState);
+                }
+                task_vars.push(("nice", *id));
+            }
+            InitialConfig::Blocked(queue, ids) => {
+                objects.push(("blocked", &queue.0));
+                blocked.extend(ids);
+            }
+            InitialConfig::Semaphore(name, _) => objects.push(("sem", &name.0)),
+            InitialConfig::Mutex(name, owner) => {
+                objects.push(("mutex", &name.0));
+                task_vars.push(("mutex", *owner));
+            }
+        }
+    }
+    let (Some(tasks), Some(current)) = (tasks_config, current_config) else {
+        return Err(Error::BadInitialState);
+    };
+    // Current and init tasks must be among the tasks, and task ids must be unique
+    if current.len() > cpus as usize
+        || current.iter().any(|id| !tasks.contains(id))
+        || init_config.is_some_and(|init| !tasks.contains(init))
+    {
+        return Err(Error::BadInitialState);
+    }
+    for (i, id) in tasks.iter().enumerate() {
+        if tasks[..i].contains(id) || current.iter().filter(|&cur| cur == id).count() > 1 {
+            return Err(Error::BadInitialState);
+        }
+    }
+    // A task may only be blocked once, and not while it runs
+    if task_vars.iter().any(|(_, id)| !tasks.contains(id))
+        || blocked
+            .iter()
+            .any(|id| !tasks.contains(id) || current.contains(id))
+        || (1..blocked.len()).any(|i| blocked[..i].contains(&blocked[i]))
+    {
+        return Err(Error::BadInitialState);
+    }
+    for (i, var) in task_vars.iter().enumerate() {
+        // A task may own several mutexes
+        if var.0 != "mutex" && task_vars[..i].contains(var) {
+            return Err(Error::BadInitialState);
+        }
+    }
+    if (1..objects.len()).any(|i| objects[..i].contains(&objects[i])) {
+        return Err(Error::BadInitialState);
+    }
     Ok(())
 }
 
@@ -53,13 +215,16 @@ fn verify(model: &Model) -> Result<(), Error> {
 mod tests {
     use super::lexer::*;
     use super::parser::*;
+    use super::*;
+    use crate::error::Error;
+    use crate::state::{AbstractState, TaskStatus};
     use std::fs::OpenOptions;
 
     #[test]
     fn parse_test() {
         let kml_file = OpenOptions::new()
             .read(true)
-            .open("src/parse/demo.kml")
+            .open("demo.kml")
             .unwrap();
         let kml = std::io::read_to_string(kml_file).unwrap();
         let tokens = tokenize_kml(&kml).unwrap();
@@ -69,4 +234,78 @@ mod tests {
         println!("{:?}", result);
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn initial_state_test() {
+        let kml = "event timer { stop sched }
+            initial { tasks = [0, 1, 2]; current = 1; }
+            kernel { scheduler = fifo events = [timer] }";
+        let model = lex_and_parse_kml(kml).unwrap();
+        let state = AbstractState::from_def(model.initial_def.as_ref().unwrap(), 1);
+        assert_eq!(state.cpus[0].current, Some(1));
+        assert_eq!(state.tasks.len(), 3);
+        assert_eq!(state.tasks[1].status, TaskStatus::Running);
+        assert_eq!(state.tasks[2].status, TaskStatus::Ready);
+        assert!(state.validate().is_ok());
+
+        let kml = "event timer { stop sched }
+            initial { tasks = [0, 1]; current = 2; }
+            kernel { scheduler = fifo events = [timer] }";
+        assert_eq!(lex_and_parse_kml(kml).unwrap_err(), Error::BadInitialState);
+
+        let kml = "event timer { stop sched }
+            initial {
+                tasks = [0, 1, 2, 3]; current = 0;
+                priority(1) = 5; nice(2) = -3;
+                blocked(io) = [3]; sem(s) = 2; mutex(m) = 1;
+            }
+            kernel { scheduler = fifo events = [timer] }";
+        let model = lex_and_parse_kml(kml).unwrap();
+        let state = AbstractState::from_def(model.initial_def.as_ref().unwrap(), 1);
+        assert_eq!(state.tasks[1].priority, 5);
+        assert_eq!(state.tasks[2].nice, -3);
+        assert_eq!(state.tasks[3].status, TaskStatus::Blocked);
+        assert_eq!(state.wait_queues["io"], vec![3]);
+        assert_eq!(state.semaphores["s"].count, 2);
+        assert_eq!(state.mutexes["m"].owner, Some(1));
+        assert_eq!(state.ready_queue, vec![1, 2]);
+        assert!(state.validate().is_ok());
+
+        // A running task cannot be blocked
+        let kml = "event timer { stop sched }
+            initial { tasks = [0, 1]; current = 0; blocked(io) = [0]; }
+            kernel { scheduler = fifo events = [timer] }";
+        assert_eq!(lex_and_parse_kml(kml).unwrap_err(), Error::BadInitialState);
+    }
+
+    #[test]
+    fn contextual_word_test() {
+        // Words other than the original keywords name events and queues too
+        let kml = "event sleep { sleep(2) sched }
+            event tick { tick }
+            event wait { block(wait) sched }
+            interrupt event time { wakeup_all(wait) }
+            event init { fork }
+            invariant at at sleep { time >= 0 }
+            initial { tasks = [0, 1]; current = 0; }
+            kernel { scheduler = rr events = [sleep, tick, wait, time, init] seed = 1 }";
+        let model = lex_and_parse_kml(kml).unwrap();
+        let names = model
+            .event_defs
+            .iter()
+            .map(|event| event.name.0.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["sleep", "tick", "wait", "time", "init"]);
+        assert!(matches!(
+            model.event_defs[2].body.as_slice(),
+            [EventAction::Block(queue), EventAction::Sched] if queue.0 == "wait"
+        ));
+        assert!(model.event_defs[3].interrupt);
+        assert_eq!(model.invariant_defs[0].at.as_deref(), Some("sleep"));
+
+        // A name that is no contextual word is no action either
+        let kml = "event nap { nap }
+            kernel { scheduler = fifo events = [nap] }";
+        assert_eq!(lex_and_parse_kml(kml).unwrap_err(), Error::SyntaxError);
+    }
 }
@@ -1,13 +1,15 @@
 use super::ast::*;
 use super::lexer::*;
 use crate::error::Error;
+use nom::branch::alt;
 use nom::bytes::complete::take;
-use nom::combinator::{eof, map, verify};
+use nom::combinator::{eof, map, opt, verify};
 use nom::error::{Error as NomError, ErrorKind};
-use nom::multi::{many0, separated_list1};
-use nom::sequence::tuple;
+use nom::multi::{fold_many0, many0, separated_list0, separated_list1};
+use nom::sequence::{preceded, tuple};
 use nom::Err;
 use nom::Finish;
+use std::str::FromStr;
 
 pub type IResult<'a, Output> = nom::IResult<Tokens<'a>, Output>;
 
@@ -21,9 +23,19 @@ pub fn parse_kml(input: Tokens) -> Result<Model, Error> {
 
 fn model(input: Tokens) -> IResult<Model> {
     map(
-        tuple((many0(event_def), kernel_def, eof)),
-        |(event_defs, kernel_def, _)| Model {
+        tuple((
+            many0(event_def),
+            many0(invariant_def),
+            many0(policy_def),
+            opt(initial_def),
+            kernel_def,
+            eof,
+        )),
+        |(event_defs, invariant_defs, policy_defs, initial_def, kernel_def, _)| Model {
             event_defs,
+            invariant_defs,
+            policy_defs,
+            initial_def,
             kernel_def,
         },
     )(input)
@@ -32,27 +44,335 @@ fn model(input: Tokens) -> IResult<Model> {
 fn event_def(input: Tokens) -> IResult<EventDef> {
     map(
         tuple((
+            opt(word("interrupt")),
             match_token(TokenKind::Event),
             identifier,
             match_token(TokenKind::LBrace),
             many0(event_action),
             match_token(TokenKind::RBrace),
         )),
-        |(_, identifier, _, actions, _)| EventDef {
+        |(interrupt, _, identifier, _, actions, _)| EventDef {
             name: identifier,
+            interrupt: interrupt.is_some(),
             body: actions,
         },
     )(input)
 }
 
+/// Actions named by contextual words rather than keywords, so that common words stay
+/// free for events, queues and other names
+const CONTEXTUAL_ACTIONS: &[&str] = &[
+    "block",
+    "wakeup_one",
+    "wakeup_all",
+    "fork",
+    "wait",
+    "setprio",
+    "setnice",
+    "settickets",
+    "sem_create",
+    "sem_down",
+    "sem_up",
+    "mutex_lock",
+    "mutex_unlock",
+    "tick",
+    "sleep",
+    "mmap",
+    "munmap",
+    "page_fault",
+    "brk",
+    "open",
+    "close",
+    "dup",
+    "pipe",
+    "read",
+    "write",
+    "kill",
+    "sigmask",
+    "sigaction",
+    "send",
+    "recv",
+    "call",
+    "reply",
+    "irq_off",
+    "irq_on",
+    "irq_enter",
+    "irq_exit",
+    "thread_create",
+    "thread_exit",
+    "exit_group",
+    "preempt",
+    "periodic",
+    "sporadic",
+    "job_done",
+    "release",
+];
+
 fn event_action(input: Tokens) -> IResult<EventAction> {
     let (input, token) = take(1usize)(input)?;
     let token = &token.tok[0];
-    if token.kind.is_action() {
-        Ok((input, token.kind.into()))
-    } else {
-        Err(Err::Error(NomError::new(input, ErrorKind::Tag)))
+    if token.kind == TokenKind::Identifier {
+        return contextual_action(&token.text().to_lowercase(), input);
+    }
+    match token.kind {
+        TokenKind::Shutdown => Ok((input, EventAction::Shutdown)),
+        TokenKind::NewTask => map(opt(parens(integer)), EventAction::NewTask)(input),
+        TokenKind::Exit => Ok((input, EventAction::Exit)),
+        TokenKind::Sched => Ok((input, EventAction::Sched)),
+        TokenKind::Stop => Ok((input, EventAction::Stop)),
+        _ => Err(Err::Error(NomError::new(input, ErrorKind::Tag))),
+    }
+}
+
+fn contextual_action<'a>(name: &str, input: Tokens<'a>) -> IResult<'a, EventAction> {
+    match name {
+        "block" => map(parens(identifier), EventAction::Block)(input),
+        "wakeup_one" => map(parens(identifier), EventAction::WakeupOne)(input),
+        "wakeup_all" => map(parens(identifier), EventAction::WakeupAll)(input),
+        "fork" => Ok((input, EventAction::Fork)),
+        "wait" => Ok((input, EventAction::Wait)),
+        "setprio" => map(parens(integer), EventAction::SetPrio)(input),
+        "setnice" => map(parens(integer), EventAction::SetNice)(input),
+        "settickets" => map(parens(number), EventAction::SetTickets)(input),
+        "sem_create" => map(
+            parens(tuple((identifier, match_token(TokenKind::Comma), number))),
+            |(name, _, count)| EventAction::SemCreate(name, count),
+        )(input),
+        "sem_down" => map(parens(identifier), EventAction::SemDown)(input),
+        "sem_up" => map(parens(identifier), EventAction::SemUp)(input),
+        "mutex_lock" => map(parens(identifier), EventAction::MutexLock)(input),
+        "mutex_unlock" => map(parens(identifier), EventAction::MutexUnlock)(input),
+        "tick" => Ok((input, EventAction::Tick)),
+        "sleep" => map(parens(number), EventAction::Sleep)(input),
+        "mmap" => map(
+            parens(tuple((
+                number,
+                match_token(TokenKind::Comma),
+                number,
+                match_token(TokenKind::Comma),
+                permission,
+            ))),
+            |(start, _, pages, _, perm)| EventAction::Mmap(start, pages, perm),
+        )(input),
+        "munmap" => map(
+            parens(tuple((number, match_token(TokenKind::Comma), number))),
+            |(start, _, pages)| EventAction::Munmap(start, pages),
+        )(input),
+        "page_fault" => map(
+            parens(tuple((
+                number,
+                match_token(TokenKind::Comma),
+                verify(permission, |perm| {
+                    perm.read as u8 + perm.write as u8 + perm.exec as u8 == 1
+                }),
+            ))),
+            |(page, _, access)| EventAction::PageFault(page, access),
+        )(input),
+        "brk" => map(parens(number), EventAction::Brk)(input),
+        "open" => map(parens(identifier), EventAction::Open)(input),
+        "close" => map(parens(number), EventAction::Close)(input),
+        "dup" => map(parens(number), EventAction::Dup)(input),
+        "pipe" => map(opt(parens(number)), EventAction::Pipe)(input),
+        "read" => map(
+            parens(tuple((number, match_token(TokenKind::Comma), number))),
+            |(fd, _, size)| EventAction::Read(fd, size),
+        )(input),
+        "write" => map(
+            parens(tuple((number, match_token(TokenKind::Comma), number))),
+            |(fd, _, size)| EventAction::Write(fd, size),
+        )(input),
+        "kill" => map(
+            parens(tuple((number, match_token(TokenKind::Comma), number))),
+            |(task, _, sig)| EventAction::Kill(task, sig),
+        )(input),
+        "sigmask" => map(parens(number_list), EventAction::SigMask)(input),
+        "sigaction" => map(
+            parens(tuple((number, match_token(TokenKind::Comma), sig_action))),
+            |(sig, _, action)| EventAction::SigAction(sig, action),
+        )(input),
+        "send" => map(
+            parens(tuple((identifier, match_token(TokenKind::Comma), number))),
+            |(endpoint, _, payload)| EventAction::Send(endpoint, payload),
+        )(input),
+        "recv" => map(parens(identifier), EventAction::Recv)(input),
+        "call" => map(
+            parens(tuple((identifier, match_token(TokenKind::Comma), number))),
+            |(endpoint, _, payload)| EventAction::Call(endpoint, payload),
+        )(input),
+        "reply" => map(parens(number), EventAction::Reply)(input),
+        "irq_off" => Ok((input, EventAction::IrqOff)),
+        "irq_on" => Ok((input, EventAction::IrqOn)),
+        "irq_enter" => Ok((input, EventAction::IrqEnter)),
+        "irq_exit" => Ok((input, EventAction::IrqExit)),
+        "thread_create" => Ok((input, EventAction::ThreadCreate)),
+        "thread_exit" => Ok((input, EventAction::ThreadExit)),
+        "exit_group" => Ok((input, EventAction::ExitGroup)),
+        "preempt" => Ok((input, EventAction::Preempt)),
+        "periodic" => map(timing, |(period, wcet, deadline)| {
+            EventAction::Periodic(period, wcet, deadline)
+        })(input),
+        "sporadic" => map(timing, |(period, wcet, deadline)| {
+            EventAction::Sporadic(period, wcet, deadline)
+        })(input),
+        "job_done" => Ok((input, EventAction::JobDone)),
+        "release" => map(parens(number), EventAction::Release)(input),
+        _ => Err(Err::Error(NomError::new(input, ErrorKind::Tag))),
+    }
+}
+
+fn invariant_def(input: Tokens) -> IResult<InvariantDef> {
+    map(
+        tuple((
+            word("invariant"),
+            identifier,
+            opt(map(tuple((word("at"), action_keyword)), |(_, keyword)| {
+                keyword
+            })),
+            match_token(TokenKind::LBrace),
+            expr,
+            match_token(TokenKind::RBrace),
+        )),
+        |(_, name, at, _, expr, _)| InvariantDef { name, at, expr },
+    )(input)
+}
+
+fn action_keyword(input: Tokens) -> IResult<String> {
+    let (input, token) = take(1usize)(input)?;
+    let token = &token.tok[0];
+    let keyword = token.text().to_lowercase();
+    let contextual =
+        token.kind == TokenKind::Identifier && CONTEXTUAL_ACTIONS.contains(&keyword.as_str());
+    if !token.kind.is_action() && !contextual {
+        return Err(Err::Error(NomError::new(input, ErrorKind::Tag)));
     }
+    Ok((input, keyword))
+}
+
+// Expressions, from the lowest precedence to the highest
+fn expr(input: Tokens) -> IResult<Expr> {
+    let (input, first) = and_expr(input)?;
+    fold_many0(
+        tuple((match_token(TokenKind::Or), and_expr)),
+        move || first.clone(),
+        |lhs, (_, rhs)| Expr::Binary(Box::new(lhs), BinaryOp::Or, Box::new(rhs)),
+    )(input)
+}
+
+fn and_expr(input: Tokens) -> IResult<Expr> {
+    let (input, first) = comparison(input)?;
+    fold_many0(
+        tuple((match_token(TokenKind::And), comparison)),
+        move || first.clone(),
+        |lhs, (_, rhs)| Expr::Binary(Box::new(lhs), BinaryOp::And, Box::new(rhs)),
+    )(input)
+}
+
+/// Comparisons do not chain
+fn comparison(input: Tokens) -> IResult<Expr> {
+    map(
+        tuple((unary, opt(tuple((comparison_op, unary))))),
+        |(lhs, rhs)| match rhs {
+            Some((op, rhs)) => Expr::Binary(Box::new(lhs), op, Box::new(rhs)),
+            None => lhs,
+        },
+    )(input)
+}
+
+fn comparison_op(input: Tokens) -> IResult<BinaryOp> {
+    let (input, token) = take(1usize)(input)?;
+    let op = match token.tok[0].kind {
+        TokenKind::EqEq => BinaryOp::Eq,
+        TokenKind::Ne => BinaryOp::Ne,
+        TokenKind::Lt => BinaryOp::Lt,
+        TokenKind::Le => BinaryOp::Le,
+        TokenKind::Gt => BinaryOp::Gt,
+        TokenKind::Ge => BinaryOp::Ge,
+        _ => return Err(Err::Error(NomError::new(input, ErrorKind::Tag))),
+    };
+    Ok((input, op))
+}
+
+fn unary(input: Tokens) -> IResult<Expr> {
+    alt((
+        map(tuple((match_token(TokenKind::Not), unary)), |(_, expr)| {
+            Expr::Not(Box::new(expr))
+        }),
+        parens(expr),
+        map(number, Expr::Number),
+        variable,
+    ))(input)
+}
+
+fn variable(input: Tokens) -> IResult<Expr> {
+    let (input, token) = take(1usize)(input)?;
+    let token = &token.tok[0];
+    match token.kind {
+        TokenKind::Identifier => Ok((input, Expr::Variable(token.text().to_lowercase()))),
+        _ => Err(Err::Error(NomError::new(input, ErrorKind::Tag))),
+    }
+}
+
+fn initial_def(input: Tokens) -> IResult<InitialDef> {
+    map(
+        tuple((
+            word("initial"),
+            match_token(TokenKind::LBrace),
+            many0(initial_config),
+            match_token(TokenKind::RBrace),
+        )),
+        |(_, _, configs, _)| InitialDef { configs },
+    )(input)
+}
+
+fn initial_config(input: Tokens) -> IResult<InitialConfig> {
+    let (input, token) = take(1usize)(input)?;
+    let token = &token.tok[0];
+    if token.kind != TokenKind::Identifier {
+        return Err(Err::Error(NomError::new(input, ErrorKind::Tag)));
+    }
+    let (input, config) = match token.text().to_lowercase().as_str() {
+        "tasks" => map(
+            tuple((match_token(TokenKind::Eq), number_list)),
+            |(_, ids)| InitialConfig::Tasks(ids),
+        )(input),
+        "current" => map(
+            tuple((
+                match_token(TokenKind::Eq),
+                alt((number_list, map(number, |id| vec![id]))),
+            )),
+            |(_, ids)| InitialConfig::Current(ids),
+        )(input),
+        "init" => map(tuple((match_token(TokenKind::Eq), number)), |(_, id)| {
+            InitialConfig::Init(id)
+        })(input),
+        "time" => map(tuple((match_token(TokenKind::Eq), number)), |(_, time)| {
+            InitialConfig::Time(time)
+        })(input),
+        "priority" => map(
+            tuple((parens(number), match_token(TokenKind::Eq), integer)),
+            |(id, _, priority)| InitialConfig::Priority(id, priority),
+        )(input),
+        "nice" => map(
+            tuple((parens(number), match_token(TokenKind::Eq), integer)),
+            |(id, _, nice)| InitialConfig::Nice(id, nice),
+        )(input),
+        "blocked" => map(
+            tuple((parens(identifier), match_token(TokenKind::Eq), number_list)),
+            |(queue, _, ids)| InitialConfig::Blocked(queue, ids),
+        )(input),
+        "sem" => map(
+            tuple((parens(identifier), match_token(TokenKind::Eq), number)),
+            |(name, _, count)| InitialConfig::Semaphore(name, count),
+        )(input),
+        "mutex" => map(
+            tuple((parens(identifier), match_token(TokenKind::Eq), number)),
+            |(name, _, owner)| InitialConfig::Mutex(name, owner),
+        )(input),
+        _ => Err(Err::Error(NomError::new(input, ErrorKind::Tag))),
+    }?;
+    // Entries may be terminated by an optional semicolon
+    let (input, _) = opt(match_token(TokenKind::Semicolon))(input)?;
+    Ok((input, config))
 }
 
 fn kernel_def(input: Tokens) -> IResult<KernelDef> {
@@ -76,6 +396,29 @@ fn kernel_config(input: Tokens) -> IResult<KernelConfig> {
         }
         TokenKind::Scheduler => scheduler_config(input)
             .map(|(input, scheduler)| (input, KernelConfig::Scheduler(scheduler))),
+        TokenKind::Identifier => match token.text().to_lowercase().as_str() {
+            "cpus" => map(tuple((match_token(TokenKind::Eq), number)), |(_, cpus)| {
+                KernelConfig::Cpus(cpus)
+            })(input),
+            "frames" => map(
+                tuple((match_token(TokenKind::Eq), number)),
+                |(_, frames)| KernelConfig::Frames(frames),
+            )(input),
+            "max_tasks" => limit_config(Resource::Tasks)(input),
+            "max_fds" => limit_config(Resource::Fds)(input),
+            "max_frames" => limit_config(Resource::Frames)(input),
+            "on_empty" => map(
+                tuple((match_token(TokenKind::Eq), on_empty)),
+                |(_, on_empty)| KernelConfig::OnEmpty(on_empty),
+            )(input),
+            "time_slice" => map(tuple((match_token(TokenKind::Eq), number)), |(_, ticks)| {
+                KernelConfig::TimeSlice(ticks)
+            })(input),
+            "seed" => map(tuple((match_token(TokenKind::Eq), number)), |(_, seed)| {
+                KernelConfig::Seed(seed)
+            })(input),
+            _ => Err(Err::Error(NomError::new(input, ErrorKind::Tag))),
+        },
         _ => Err(Err::Error(NomError::new(input, ErrorKind::Tag))),
     }
 }
@@ -92,6 +435,34 @@ fn event_config(input: Tokens) -> IResult<Vec<Identifier>> {
     )(input)
 }
 
+fn limit_config(resource: Resource) -> impl FnMut(Tokens) -> IResult<KernelConfig> {
+    move |input| {
+        map(
+            tuple((
+                match_token(TokenKind::Eq),
+                number,
+                opt(tuple((match_token(TokenKind::DotDot), number))),
+            )),
+            |(_, min, max)| {
+                let max = max.map_or(min, |(_, max)| max);
+                KernelConfig::Limit(resource, Limit { min, max })
+            },
+        )(input)
+    }
+}
+
+/// Policy written as `idle`, `shutdown(code)` or `error`
+fn on_empty(input: Tokens) -> IResult<OnEmpty> {
+    let (rest, token) = take(1usize)(input)?;
+    let token = &token.tok[0];
+    match (token.kind, token.text()) {
+        (TokenKind::Identifier, "idle") => Ok((rest, OnEmpty::Idle)),
+        (TokenKind::Identifier, "error") => Ok((rest, OnEmpty::Error)),
+        (TokenKind::Shutdown, _) => map(parens(integer), OnEmpty::Shutdown)(rest),
+        _ => Err(Err::Error(NomError::new(rest, ErrorKind::Tag))),
+    }
+}
+
 fn scheduler_config(input: Tokens) -> IResult<SchedulerType> {
     map(
         tuple((match_token(TokenKind::Eq), scheduler_type)),
@@ -102,9 +473,91 @@ fn scheduler_config(input: Tokens) -> IResult<SchedulerType> {
 fn scheduler_type(input: Tokens) -> IResult<SchedulerType> {
     let (input, token) = take(1usize)(input)?;
     let token = &token.tok[0];
-    match token.kind {
-        TokenKind::Fifo => Ok((input, SchedulerType::Fifo)),
-        TokenKind::Random => Ok((input, SchedulerType::Random)),
+    if token.kind == TokenKind::Fifo {
+        return Ok((input, SchedulerType::Fifo));
+    }
+    if token.kind != TokenKind::Identifier {
+        return Err(Err::Error(NomError::new(input, ErrorKind::Tag)));
+    }
+    match token.text().to_lowercase().as_str() {
+        "random" => Ok((input, SchedulerType::Random)),
+        "rr" => Ok((input, SchedulerType::Rr)),
+        "cfs" => Ok((input, SchedulerType::Cfs)),
+        "edf" => Ok((input, SchedulerType::Edf)),
+        "rm" => Ok((input, SchedulerType::Rm)),
+        "lottery" => Ok((input, SchedulerType::Lottery)),
+        "stride" => Ok((input, SchedulerType::Stride)),
+        "priority" => map(opt(parens(tie_break)), |tie_break| {
+            SchedulerType::Priority(tie_break.unwrap_or_default())
+        })(input),
+        "mlfq" => map(
+            parens(tuple((
+                number_list,
+                opt(tuple((match_token(TokenKind::Comma), number))),
+            ))),
+            |(quanta, boost)| SchedulerType::Mlfq {
+                quanta,
+                boost: boost.map(|(_, boost)| boost),
+            },
+        )(input),
+        _ => Ok((input, SchedulerType::Custom(token.text().to_string()))),
+    }
+}
+
+/// Scheduler written as `scheduler name { pick min(priority) then max(id) among ready }`,
+/// ending with `preempt` if it preempts
+fn policy_def(input: Tokens) -> IResult<PolicyDef> {
+    map(
+        tuple((
+            match_token(TokenKind::Scheduler),
+            identifier,
+            match_token(TokenKind::LBrace),
+            word("pick"),
+            separated_list1(word("then"), sort_key),
+            opt(tuple((word("among"), word("ready")))),
+            opt(word("preempt")),
+            match_token(TokenKind::RBrace),
+        )),
+        |(_, name, _, _, keys, _, preempt, _)| PolicyDef {
+            name,
+            keys,
+            preemptive: preempt.is_some(),
+        },
+    )(input)
+}
+
+fn sort_key(input: Tokens) -> IResult<SortKey> {
+    alt((
+        map(preceded(word("min"), parens(task_field)), SortKey::Min),
+        map(preceded(word("max"), parens(task_field)), SortKey::Max),
+    ))(input)
+}
+
+fn task_field(input: Tokens) -> IResult<TaskField> {
+    let (input, token) = take(1usize)(input)?;
+    let token = &token.tok[0];
+    let field = match (token.kind, token.text()) {
+        (TokenKind::Identifier, "priority") => TaskField::Priority,
+        (TokenKind::Identifier, "id") => TaskField::Id,
+        (TokenKind::Identifier, "nice") => TaskField::Nice,
+        (TokenKind::Identifier, "tickets") => TaskField::Tickets,
+        (TokenKind::Identifier, "deadline") => TaskField::Deadline,
+        (TokenKind::Identifier, "period") => TaskField::Period,
+        (TokenKind::Identifier, "queue") => TaskField::Queue,
+        (TokenKind::Identifier, "ready_time") => TaskField::ReadyTime,
+        (TokenKind::Identifier, "scheduled") => TaskField::Scheduled,
+        _ => return Err(Err::Error(NomError::new(input, ErrorKind::Tag))),
+    };
+    Ok((input, field))
+}
+
+/// Tie-break written as `fifo` or `lowest_id`
+fn tie_break(input: Tokens) -> IResult<TieBreak> {
+    let (input, token) = take(1usize)(input)?;
+    let token = &token.tok[0];
+    match (token.kind, token.text()) {
+        (TokenKind::Fifo, _) => Ok((input, TieBreak::Fifo)),
+        (TokenKind::Identifier, "lowest_id") => Ok((input, TieBreak::LowestId)),
         _ => Err(Err::Error(NomError::new(input, ErrorKind::Tag))),
     }
 }
@@ -114,6 +567,28 @@ fn match_token(kind: TokenKind) -> impl FnMut(Tokens) -> IResult<Tokens> {
     move |i| verify(take(1usize), |t: &Tokens| t.tok[0].kind == kind)(i)
 }
 
+/// An identifier used as a contextual keyword, so that it stays free for names
+fn word(text: &'static str) -> impl FnMut(Tokens) -> IResult<Tokens> {
+    move |i| {
+        verify(take(1usize), |t: &Tokens| {
+            t.tok[0].kind == TokenKind::Identifier && t.tok[0].text().eq_ignore_ascii_case(text)
+        })(i)
+    }
+}
+
+fn parens<'a, O>(
+    inner: impl FnMut(Tokens<'a>) -> IResult<'a, O>,
+) -> impl FnMut(Tokens<'a>) -> IResult<'a, O> {
+    map(
+        tuple((
+            match_token(TokenKind::LParen),
+            inner,
+            match_token(TokenKind::RParen),
+        )),
+        |(_, o, _)| o,
+    )
+}
+
 // Low-level terminals
 fn identifier(input: Tokens) -> IResult<Identifier> {
     let (input, token) = take(1usize)(input)?;
@@ -127,3 +602,96 @@ fn identifier(input: Tokens) -> IResult<Identifier> {
 fn identifiers(input: Tokens) -> IResult<Vec<Identifier>> {
     separated_list1(match_token(TokenKind::Comma), identifier)(input)
 }
+
+fn number<N: FromStr>(input: Tokens) -> IResult<N> {
+    let (input, token) = take(1usize)(input)?;
+    let token = &token.tok[0];
+    match token.kind {
+        TokenKind::Number => match token.text().parse() {
+            Ok(n) => Ok((input, n)),
+            Err(_) => Err(Err::Error(NomError::new(input, ErrorKind::Digit))),
+        },
+        _ => Err(Err::Error(NomError::new(input, ErrorKind::Tag))),
+    }
+}
+
+fn integer(input: Tokens) -> IResult<i32> {
+    let (input, (minus, token)) = tuple((opt(match_token(TokenKind::Minus)), take(1usize)))(input)?;
+    let token = &token.tok[0];
+    let text = match minus {
+        Some(_) => format!("-{}", token.text()),
+        None => token.text().to_string(),
+    };
+    match token.kind {
+        TokenKind::Number => match text.parse() {
+            Ok(n) => Ok((input, n)),
+            Err(_) => Err(Err::Error(NomError::new(input, ErrorKind::Digit))),
+        },
+        _ => Err(Err::Error(NomError::new(input, ErrorKind::Tag))),
+    }
+}
+
+fn numbers(input: Tokens) -> IResult<Vec<u32>> {
+    separated_list0(match_token(TokenKind::Comma), number)(input)
+}
+
+/// Permission written as a combination of `r`, `w` and `x`, e.g. `rw`
+fn permission(input: Tokens) -> IResult<Permission> {
+    let (input, token) = take(1usize)(input)?;
+    let token = &token.tok[0];
+    let text = token.text();
+    let valid = token.kind == TokenKind::Identifier
+        && text.chars().all(|c| "rwx".contains(c))
+        && text
+            .chars()
+            .enumerate()
+            .all(|(i, c)| !text[..i].contains(c));
+    if !valid {
+        return Err(Err::Error(NomError::new(input, ErrorKind::Tag)));
+    }
+    Ok((
+        input,
+        Permission {
+            read: text.contains('r'),
+            write: text.contains('w'),
+            exec: text.contains('x'),
+        },
+    ))
+}
+
+/// Real-time parameters written as `(period, wcet)` or `(period, wcet, deadline)`
+fn timing(input: Tokens) -> IResult<(u32, u32, Option<u32>)> {
+    map(
+        parens(tuple((
+            number,
+            match_token(TokenKind::Comma),
+            number,
+            opt(tuple((match_token(TokenKind::Comma), number))),
+        ))),
+        |(period, _, wcet, deadline)| (period, wcet, deadline.map(|(_, deadline)| deadline)),
+    )(input)
+}
+
+/// Signal disposition written as `default`, `ignore` or `handle`
+fn sig_action(input: Tokens) -> IResult<SigAction> {
+    let (input, token) = take(1usize)(input)?;
+    let token = &token.tok[0];
+    let action = match (token.kind, token.text()) {
+        (TokenKind::Identifier, "default") => SigAction::Default,
+        (TokenKind::Identifier, "ignore") => SigAction::Ignore,
+        (TokenKind::Identifier, "handle") => SigAction::Handle,
+        _ => return Err(Err::Error(NomError::new(input, ErrorKind::Tag))),
+    };
+    Ok((input, action))
+}
+
+fn number_list(input: Tokens) -> IResult<Vec<u32>> {
+    map(
+        tuple((
+            match_token(TokenKind::LBracket),
+            numbers,
+            match_token(TokenKind::RBracket),
+        )),
+        |(_, ids, _)| ids,
+    )(input)
+}
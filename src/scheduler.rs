@@ -1,46 +1,785 @@
-use crate::{parse::SchedulerType, state::*};
-use rand::Rng;
+use crate::{
+    parse::{PolicyDef, SchedulerType, SortKey, TaskField, TieBreak},
+    state::*,
+};
+use std::collections::HashMap;
+use std::fmt::Debug;
 
+/// Weight of a nice 0 task
+pub const NICE_0_WEIGHT: u64 = 1024;
+
+/// Weight of each nice value from -20 to 19, as in Linux. Every step of nice
+/// changes the share of cpu time by about 10%.
+const NICE_TO_WEIGHT: [u64; 40] = [
+    88761, 71755, 56483, 46273, 36291, 29154, 23254, 18705, 14949, 11916, 9548, 7620, 6100, 4904,
+    3906, 3121, 2501, 1991, 1586, 1277, 1024, 820, 655, 526, 423, 335, 272, 215, 172, 137, 110, 87,
+    70, 56, 45, 36, 29, 23, 18, 15,
+];
+
+/// A scheduling policy. Only `pick` is required, the hooks default to doing nothing.
+/// State a policy keeps in itself is not part of the kernel state, a policy that keeps
+/// any must expose it through `key` for exploration to tell such states apart.
+pub trait Scheduler: SchedulerClone + Debug {
+    /// Returns the ids of all tasks the policy allows to run next on `cpu`.
+    /// The kernel picks one of them, so a deterministic policy returns at most one.
+    fn pick(&mut self, state: &AbstractState, cpu: CpuId) -> Vec<TaskId>;
+    /// Relative chance of each candidate to be picked when simulating, the same for
+    /// all unless the policy draws lots
+    fn weights(&self, _state: &AbstractState, candidates: &[TaskId]) -> Vec<u32> {
+        vec![1; candidates.len()]
+    }
+    /// Whether the task running on `cpu` must give way to a ready task
+    fn preempts(&self, _state: &AbstractState, _cpu: CpuId) -> bool {
+        false
+    }
+    /// Ticks a task may run before `preempt` switches it out, `None` if the policy
+    /// uses the configured time slice
+    fn quantum(&self, _task: &TaskControlBlock) -> Option<u32> {
+        None
+    }
+    /// A running task used up its quantum
+    fn expired(&mut self, _task: &mut TaskControlBlock) {}
+    /// A running task blocked before using up its quantum
+    fn blocked(&mut self, _task: &mut TaskControlBlock) {}
+    /// The clock advanced
+    fn ticked(&mut self, _state: &mut AbstractState) {}
+    /// A task was created
+    fn created(&mut self, _state: &mut AbstractState, _id: TaskId) {}
+    /// Whether the policy can schedule a task of a given initial state
+    fn admits(&self, _task: &TaskControlBlock) -> bool {
+        true
+    }
+    /// Encoding of the state the policy keeps in itself, equal for two policies that
+    /// make the same choices from the same kernel state
+    fn key(&self) -> Vec<u8> {
+        Vec::new()
+    }
+}
+
+/// Cloning of boxed schedulers, implemented for every `Scheduler` that is `Clone`
+pub trait SchedulerClone {
+    fn clone_box(&self) -> Box<dyn Scheduler>;
+}
+
+impl<T: Scheduler + Clone + 'static> SchedulerClone for T {
+    fn clone_box(&self) -> Box<dyn Scheduler> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn Scheduler> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+/// Builds a scheduler from its configuration in kml, `None` if the configuration
+/// does not suit it
+pub type Factory = fn(&SchedulerType) -> Option<Box<dyn Scheduler>>;
+
+/// Schedulers by the name used in `scheduler = <name>`. Built-in policies are
+/// registered by default, a policy written in Rust is plugged in with `register`.
 #[derive(Clone)]
-pub struct Scheduler {
-    type_: SchedulerType,
+pub struct Registry {
+    factories: HashMap<String, Factory>,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        let mut registry = Self {
+            factories: HashMap::new(),
+        };
+        for name in [
+            "fifo", "random", "rr", "priority", "mlfq", "cfs", "edf", "rm", "lottery", "stride",
+        ] {
+            registry.register(name, builtin);
+        }
+        registry
+    }
+}
+
+impl Registry {
+    /// Register a scheduler under `name`, replacing any scheduler of that name
+    pub fn register(&mut self, name: &str, factory: Factory) -> &mut Self {
+        self.factories.insert(name.to_string(), factory);
+        self
+    }
+    /// Build the scheduler configured in kml, `None` if no scheduler has its name or
+    /// its factory rejects the configuration
+    pub fn build(&self, type_: &SchedulerType) -> Option<Box<dyn Scheduler>> {
+        self.factories
+            .get(type_.name())
+            .and_then(|factory| factory(type_))
+    }
+}
+
+fn builtin(type_: &SchedulerType) -> Option<Box<dyn Scheduler>> {
+    let scheduler: Box<dyn Scheduler> = match type_ {
+        SchedulerType::Fifo => Box::new(Fifo),
+        SchedulerType::Random => Box::new(Random),
+        SchedulerType::Rr => Box::new(Rr),
+        SchedulerType::Priority(tie_break) => Box::new(Priority(*tie_break)),
+        SchedulerType::Mlfq { quanta, boost } => Box::new(Mlfq {
+            quanta: quanta.clone(),
+            boost: *boost,
+        }),
+        SchedulerType::Cfs => Box::new(Cfs),
+        SchedulerType::Edf => Box::new(Edf),
+        SchedulerType::Rm => Box::new(Rm),
+        SchedulerType::Lottery => Box::new(Lottery),
+        SchedulerType::Stride => Box::new(Stride),
+        SchedulerType::Custom(_) => return None,
+    };
+    Some(scheduler)
+}
+
+/// First ready task in task order
+#[derive(Debug, Clone)]
+struct Fifo;
+
+impl Scheduler for Fifo {
+    fn pick(&mut self, state: &AbstractState, _cpu: CpuId) -> Vec<TaskId> {
+        state.ready_tasks().into_iter().take(1).collect()
+    }
+}
+
+/// Any ready task
+#[derive(Debug, Clone)]
+struct Random;
+
+impl Scheduler for Random {
+    fn pick(&mut self, state: &AbstractState, _cpu: CpuId) -> Vec<TaskId> {
+        state.ready_tasks()
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Rr;
+
+impl Scheduler for Rr {
+    fn pick(&mut self, state: &AbstractState, _cpu: CpuId) -> Vec<TaskId> {
+        state.ready_queue.first().copied().into_iter().collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Priority(TieBreak);
+
+impl Scheduler for Priority {
+    fn pick(&mut self, state: &AbstractState, _cpu: CpuId) -> Vec<TaskId> {
+        let ready = state.ready_queue.iter().map(|&id| state.task(id).unwrap());
+        let best = match self.0 {
+            // The first of equal maxima in queue order
+            TieBreak::Fifo => ready.rev().max_by_key(|task| task.priority),
+            TieBreak::LowestId => {
+                ready.max_by_key(|task| (task.priority, std::cmp::Reverse(task.id)))
+            }
+        };
+        best.map(|task| task.id).into_iter().collect()
+    }
+    fn preempts(&self, state: &AbstractState, cpu: CpuId) -> bool {
+        running(state, cpu).is_some_and(|running| {
+            state
+                .tasks
+                .iter()
+                .any(|task| task.status == TaskStatus::Ready && task.priority > running.priority)
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Mlfq {
+    quanta: Vec<u32>,
+    boost: Option<u32>,
 }
 
-impl Scheduler {
-    pub fn new(type_: SchedulerType) -> Self {
-        Self { type_ }
+impl Scheduler for Mlfq {
+    fn pick(&mut self, state: &AbstractState, _cpu: CpuId) -> Vec<TaskId> {
+        first_by_key(state, |task| task.level as u64)
+    }
+    fn preempts(&self, state: &AbstractState, cpu: CpuId) -> bool {
+        running(state, cpu).is_some_and(|running| {
+            state
+                .tasks
+                .iter()
+                .any(|task| task.status == TaskStatus::Ready && task.level < running.level)
+        })
+    }
+    fn quantum(&self, task: &TaskControlBlock) -> Option<u32> {
+        Some(self.quanta[task.level as usize])
     }
-    pub fn type_(&self) -> SchedulerType {
-        self.type_
+    fn expired(&mut self, task: &mut TaskControlBlock) {
+        task.level = (task.level + 1).min(self.quanta.len() as u32 - 1);
     }
-    /// Returns the id of the next task to be executed
-    pub fn schedule(&self, state: &AbstractState) -> Option<TaskId> {
-        match self.type_ {
-            SchedulerType::Fifo => Self::fifo(state),
-            SchedulerType::Random => Self::random(state),
+    fn blocked(&mut self, task: &mut TaskControlBlock) {
+        task.level = task.level.saturating_sub(1);
+    }
+    fn admits(&self, task: &TaskControlBlock) -> bool {
+        (task.level as usize) < self.quanta.len()
+    }
+    fn ticked(&mut self, state: &mut AbstractState) {
+        if let Some(boost) = self.boost {
+            if state.time.is_multiple_of(boost as u64) {
+                for task in state.tasks.iter_mut() {
+                    task.level = 0;
+                }
+            }
         }
     }
+}
 
-    // Methods
-    fn fifo(state: &AbstractState) -> Option<TaskId> {
-        state
-            .tasks
+#[derive(Debug, Clone)]
+struct Cfs;
+
+impl Scheduler for Cfs {
+    fn pick(&mut self, state: &AbstractState, _cpu: CpuId) -> Vec<TaskId> {
+        first_by_key(state, |task| task.vruntime)
+    }
+    fn ticked(&mut self, state: &mut AbstractState) {
+        for task in state.tasks.iter_mut() {
+            if task.status == TaskStatus::Running {
+                let weight = NICE_TO_WEIGHT[(task.nice + 20) as usize];
+                task.vruntime += NICE_0_WEIGHT * NICE_0_WEIGHT / weight;
+            }
+        }
+    }
+    fn created(&mut self, state: &mut AbstractState, id: TaskId) {
+        state.task_mut(id).unwrap().vruntime = min_of_others(state, id, |task| task.vruntime);
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Edf;
+
+impl Scheduler for Edf {
+    fn pick(&mut self, state: &AbstractState, _cpu: CpuId) -> Vec<TaskId> {
+        first_by_key(state, edf_urgency)
+    }
+    fn preempts(&self, state: &AbstractState, cpu: CpuId) -> bool {
+        more_urgent_ready(state, cpu, edf_urgency)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Rm;
+
+impl Scheduler for Rm {
+    fn pick(&mut self, state: &AbstractState, _cpu: CpuId) -> Vec<TaskId> {
+        first_by_key(state, rm_urgency)
+    }
+    fn preempts(&self, state: &AbstractState, cpu: CpuId) -> bool {
+        more_urgent_ready(state, cpu, rm_urgency)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Lottery;
+
+impl Scheduler for Lottery {
+    fn pick(&mut self, state: &AbstractState, _cpu: CpuId) -> Vec<TaskId> {
+        state.ready_queue.clone()
+    }
+    fn weights(&self, state: &AbstractState, candidates: &[TaskId]) -> Vec<u32> {
+        candidates
+            .iter()
+            .map(|&id| state.task(id).unwrap().tickets)
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Stride;
+
+impl Scheduler for Stride {
+    fn pick(&mut self, state: &AbstractState, _cpu: CpuId) -> Vec<TaskId> {
+        first_by_key(state, |task| task.pass)
+    }
+    fn ticked(&mut self, state: &mut AbstractState) {
+        for task in state.tasks.iter_mut() {
+            if task.status == TaskStatus::Running {
+                task.pass += task.stride();
+            }
+        }
+    }
+    fn created(&mut self, state: &mut AbstractState, id: TaskId) {
+        state.task_mut(id).unwrap().pass = min_of_others(state, id, |task| task.pass);
+    }
+}
+
+/// Scheduler declared in kml
+#[derive(Debug, Clone)]
+pub struct Policy(PolicyDef);
+
+impl Policy {
+    pub fn new(def: PolicyDef) -> Self {
+        Self(def)
+    }
+    /// Position of a task in the order of the policy, compared key by key
+    fn order(&self, state: &AbstractState, task: &TaskControlBlock) -> Vec<i64> {
+        self.0
+            .keys
             .iter()
-            .find(|task| task.status == TaskStatus::Ready)
-            .map(|task| task.id)
+            .map(|key| match *key {
+                SortKey::Min(field) => field_value(state, task, field),
+                SortKey::Max(field) => -field_value(state, task, field),
+            })
+            .collect()
     }
-    fn random(state: &AbstractState) -> Option<TaskId> {
+}
+
+impl Scheduler for Policy {
+    fn pick(&mut self, state: &AbstractState, _cpu: CpuId) -> Vec<TaskId> {
         let ready = state
-            .tasks
+            .ready_queue
             .iter()
-            .filter(|task| task.status == TaskStatus::Ready)
+            .map(|&id| (id, self.order(state, state.task(id).unwrap())))
             .collect::<Vec<_>>();
-        if ready.is_empty() {
-            return None;
-        }
+        let Some(first) = ready.iter().map(|(_, order)| order).min() else {
+            return Vec::new();
+        };
         ready
             .iter()
-            .nth(rand::thread_rng().gen_range(0..ready.len()))
-            .map(|task| task.id)
+            .filter(|(_, order)| order == first)
+            .map(|(id, _)| *id)
+            .collect()
+    }
+    fn preempts(&self, state: &AbstractState, cpu: CpuId) -> bool {
+        self.0.preemptive
+            && running(state, cpu).is_some_and(|running| {
+                let order = self.order(state, running);
+                state
+                    .ready_queue
+                    .iter()
+                    .any(|&id| self.order(state, state.task(id).unwrap()) < order)
+            })
+    }
+}
+
+fn field_value(state: &AbstractState, task: &TaskControlBlock, field: TaskField) -> i64 {
+    match field {
+        TaskField::Id => task.id as i64,
+        TaskField::Priority => task.priority as i64,
+        TaskField::Nice => task.nice as i64,
+        TaskField::Tickets => task.tickets as i64,
+        TaskField::Deadline => task
+            .rt
+            .as_ref()
+            .map_or(i64::MAX, |rt| rt.absolute_deadline() as i64),
+        TaskField::Period => task.rt.as_ref().map_or(i64::MAX, |rt| rt.period as i64),
+        // A running task is ahead of the whole queue
+        TaskField::Queue => state
+            .ready_queue
+            .iter()
+            .position(|&id| id == task.id)
+            .map_or(-1, |position| position as i64),
+        TaskField::ReadyTime => task.stats.ready_time as i64,
+        TaskField::Scheduled => task.stats.scheduled as i64,
+    }
+}
+
+// Helpers shared by policies
+
+/// Task still running on `cpu`
+fn running(state: &AbstractState, cpu: CpuId) -> Option<&TaskControlBlock> {
+    state.cpus[cpu]
+        .current
+        .and_then(|id| state.task(id))
+        .filter(|task| task.status == TaskStatus::Running)
+}
+
+/// The ready task with the smallest key, the first in queue order among equals
+fn first_by_key(state: &AbstractState, key: impl Fn(&TaskControlBlock) -> u64) -> Vec<TaskId> {
+    state
+        .ready_queue
+        .iter()
+        .map(|&id| state.task(id).unwrap())
+        .min_by_key(|task| key(task))
+        .map(|task| task.id)
+        .into_iter()
+        .collect()
+}
+
+/// Smallest key among the other ready and running tasks, so that a new task starts
+/// even with the task that is furthest behind and neither starves others nor is starved
+fn min_of_others(state: &AbstractState, id: TaskId, key: fn(&TaskControlBlock) -> u64) -> u64 {
+    state
+        .tasks
+        .iter()
+        .filter(|task| task.id != id)
+        .filter(|task| matches!(task.status, TaskStatus::Ready | TaskStatus::Running))
+        .map(key)
+        .min()
+        .unwrap_or(0)
+}
+
+/// Absolute deadline of the job of a real-time task, other tasks come last
+fn edf_urgency(task: &TaskControlBlock) -> u64 {
+    task.rt
+        .as_ref()
+        .map_or(u64::MAX, |rt| rt.absolute_deadline())
+}
+
+/// Period of a real-time task, other tasks come last
+fn rm_urgency(task: &TaskControlBlock) -> u64 {
+    task.rt.as_ref().map_or(u64::MAX, |rt| rt.period as u64)
+}
+
+fn more_urgent_ready(
+    state: &AbstractState,
+    cpu: CpuId,
+    urgency: fn(&TaskControlBlock) -> u64,
+) -> bool {
+    running(state, cpu).is_some_and(|running| {
+        state
+            .tasks
+            .iter()
+            .any(|task| task.status == TaskStatus::Ready && urgency(task) < urgency(running))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+    use crate::kernel::Kernel;
+    use crate::parse::lex_and_parse_kml;
+
+    /// Picks a task that does not exist
+    #[derive(Debug, Clone)]
+    struct Unknown;
+    impl Scheduler for Unknown {
+        fn pick(&mut self, _state: &AbstractState, _cpu: CpuId) -> Vec<TaskId> {
+            vec![9]
+        }
+    }
+
+    /// Gives every ready task no chance at all
+    #[derive(Debug, Clone)]
+    struct Unweighted;
+    impl Scheduler for Unweighted {
+        fn pick(&mut self, state: &AbstractState, _cpu: CpuId) -> Vec<TaskId> {
+            state.ready_tasks()
+        }
+        fn weights(&self, _state: &AbstractState, candidates: &[TaskId]) -> Vec<u32> {
+            vec![0; candidates.len()]
+        }
+    }
+
+    /// Round robin of even tasks on cpu 0 and odd ones on cpu 1
+    #[derive(Debug, Clone)]
+    struct Pinned;
+    impl Scheduler for Pinned {
+        fn pick(&mut self, state: &AbstractState, cpu: CpuId) -> Vec<TaskId> {
+            let mut ready = state.ready_queue.iter().copied();
+            ready
+                .find(|&id| id % 2 == cpu as TaskId)
+                .into_iter()
+                .collect()
+        }
+    }
+
+    /// Kernel of `kml` run by `scheduler`, which is added to its kernel block. The
+    /// schedulers above are registered next to the built-in ones.
+    fn with_scheduler(scheduler: &str, kml: &str) -> Kernel {
+        let kml = kml.replacen(
+            "kernel {",
+            &format!("kernel {{ scheduler = {}", scheduler),
+            1,
+        );
+        let mut registry = Registry::default();
+        registry.register("unknown", |_| Some(Box::new(Unknown)));
+        registry.register("unweighted", |_| Some(Box::new(Unweighted)));
+        registry.register("pinned", |_| Some(Box::new(Pinned)));
+        Kernel::from_model(lex_and_parse_kml(&kml).unwrap(), None, &registry).unwrap()
+    }
+
+    #[test]
+    fn round_robin_test() {
+        let mut kernel = with_scheduler(
+            "rr",
+            "event timer { tick preempt }
+            event relinquish { stop sched }
+            initial { tasks = [0, 1, 2]; current = 0; }
+            kernel { time_slice = 2 events = [timer, relinquish] }",
+        );
+        kernel.execute("timer").unwrap();
+        assert_eq!(kernel.state().cpus[0].current, Some(0));
+        kernel.execute("timer").unwrap();
+        assert_eq!(kernel.state().cpus[0].current, Some(1));
+        assert_eq!(kernel.state().ready_queue, vec![2, 0]);
+        // Giving up the cpu early also moves a task to the back of the queue
+        kernel.execute("relinquish").unwrap();
+        assert_eq!(kernel.state().cpus[0].current, Some(2));
+        assert_eq!(kernel.state().ready_queue, vec![0, 1]);
+        kernel.execute("timer").unwrap();
+        assert_eq!(kernel.state().task(2).unwrap().slice_used, 1);
+    }
+    #[test]
+    fn priority_scheduler_test() {
+        let kml = "event spawn { newtask(5) }
+            event io { block(io) sched }
+            event irq { wakeup_one(io) }
+            initial { tasks = [0, 1, 2]; current = 0; }
+            kernel { events = [spawn, io, irq] }";
+        let mut kernel = with_scheduler("priority(lowest_id)", kml);
+        // A more important task preempts current one as soon as it is created
+        kernel.execute("spawn").unwrap();
+        assert_eq!(kernel.state().cpus[0].current, Some(3));
+        kernel.execute("io").unwrap();
+        assert_eq!(kernel.state().cpus[0].current, Some(0));
+        // or woken up
+        kernel.execute("irq").unwrap();
+        assert_eq!(kernel.state().cpus[0].current, Some(3));
+        let mut kernel = with_scheduler("priority", kml);
+        kernel.execute("spawn").unwrap();
+        kernel.execute("io").unwrap();
+        assert_eq!(kernel.state().cpus[0].current, Some(1));
+    }
+    #[test]
+    fn mlfq_test() {
+        let mut kernel = with_scheduler(
+            "mlfq([1,",
+            "event timer { tick preempt }
+            event io { block(io) sched }
+            event irq { wakeup_one(io) }
+            initial { tasks = [0, 1]; current = 0; }
+            kernel { 2], 4) events = [timer, io, irq] }",
+        );
+        let levels = |kernel: &Kernel| {
+            let state = kernel.state();
+            (
+                state.cpus[0].current,
+                state.task(0).unwrap().level,
+                state.task(1).unwrap().level,
+            )
+        };
+        // Using up a quantum demotes
+        kernel.execute("timer").unwrap();
+        assert_eq!(levels(&kernel), (Some(1), 1, 0));
+        kernel.execute("timer").unwrap();
+        assert_eq!(levels(&kernel), (Some(0), 1, 1));
+        // Blocking promotes, and a higher level preempts once ready
+        kernel.execute("io").unwrap();
+        assert_eq!(levels(&kernel), (Some(1), 0, 1));
+        kernel.execute("irq").unwrap();
+        assert_eq!(levels(&kernel), (Some(0), 0, 1));
+        kernel.execute("timer").unwrap();
+        assert_eq!(levels(&kernel), (Some(1), 1, 1));
+        // Every 4 ticks all tasks are boosted, before current one uses up its quantum
+        kernel.execute("timer").unwrap();
+        assert_eq!(levels(&kernel), (Some(0), 0, 1));
+    }
+    #[test]
+    fn cfs_test() {
+        let mut kernel = with_scheduler(
+            "cfs",
+            "event renice { setnice(5) }
+            event timer { tick preempt }
+            event spawn { newtask }
+            initial { tasks = [0, 1]; current = 0; }
+            kernel { events = [renice, timer, spawn] }",
+        );
+        let vruntimes = |kernel: &Kernel| {
+            let state = kernel.state();
+            let vruntimes = state
+                .tasks
+                .iter()
+                .map(|task| task.vruntime)
+                .collect::<Vec<_>>();
+            (state.cpus[0].current, vruntimes)
+        };
+        kernel.execute("renice").unwrap();
+        kernel.execute("timer").unwrap();
+        assert_eq!(vruntimes(&kernel), (Some(1), vec![3130, 0]));
+        // A nice 0 task keeps the cpu until it catches up
+        kernel.execute("timer").unwrap();
+        kernel.execute("timer").unwrap();
+        kernel.execute("timer").unwrap();
+        assert_eq!(vruntimes(&kernel), (Some(1), vec![3130, 3072]));
+        kernel.execute("timer").unwrap();
+        assert_eq!(vruntimes(&kernel), (Some(0), vec![3130, 4096]));
+        // New tasks start at the smallest virtual runtime
+        kernel.execute("spawn").unwrap();
+        assert_eq!(vruntimes(&kernel), (Some(0), vec![3130, 4096, 3130]));
+    }
+    #[test]
+    fn real_time_test() {
+        let mut kernel = with_scheduler(
+            "edf",
+            "event hard { periodic(4, 3) }
+            event soft { sporadic(6, 1, 1) }
+            event done { job_done sched }
+            event timer { tick }
+            event release { release(1) }
+            initial { tasks = [0, 1]; current = 0; }
+            kernel { events = [hard, soft, done, timer, release] }",
+        );
+        kernel.execute("hard").unwrap();
+        kernel.execute("done").unwrap();
+        assert_eq!(kernel.state().task(0).unwrap().wake_at, Some(4));
+        kernel.execute("soft").unwrap();
+        // Running past the deadline misses it, and past the worst-case execution
+        // time overruns it, which are counted apart
+        kernel.execute("timer").unwrap();
+        assert_eq!(kernel.state().deadline_misses(), 0);
+        kernel.execute("timer").unwrap();
+        assert_eq!(kernel.state().deadline_misses(), 1);
+        assert_eq!(kernel.state().wcet_overruns(), 1);
+        // The periodic task is released again, but has a later deadline
+        kernel.execute("timer").unwrap();
+        kernel.execute("timer").unwrap();
+        assert_eq!(kernel.state().cpus[0].current, Some(1));
+        kernel.execute("done").unwrap();
+        assert_eq!(kernel.state().cpus[0].current, Some(0));
+        // Sporadic jobs arrive at least a period apart
+        assert_eq!(kernel.execute("release"), Err(Error::BadArgument));
+        kernel.execute("timer").unwrap();
+        kernel.execute("timer").unwrap();
+        kernel.execute("release").unwrap();
+        assert_eq!(kernel.state().cpus[0].current, Some(1));
+        assert_eq!(
+            kernel
+                .state()
+                .task(0)
+                .unwrap()
+                .rt
+                .as_ref()
+                .unwrap()
+                .executed,
+            2
+        );
+        assert_eq!(kernel.state().deadline_misses(), 1);
+    }
+    #[test]
+    fn proportional_share_test() {
+        let kml = "event rich { settickets(300) }
+            event timer { tick preempt }
+            initial { tasks = [0, 1, 2]; current = 0; }
+            kernel { seed = 7 events = [rich, timer] }";
+        let schedule = |mut kernel: Kernel| {
+            kernel.execute("rich").unwrap();
+            (0..8)
+                .map(|_| {
+                    kernel.execute("timer").unwrap();
+                    kernel.state().cpus[0].current.unwrap()
+                })
+                .collect::<Vec<_>>()
+        };
+        // Any ready task may win the lottery, simulating with a seed is reproducible
+        assert_eq!(
+            with_scheduler("lottery", kml).successors("timer", 0).len(),
+            3
+        );
+        assert_eq!(
+            schedule(with_scheduler("lottery", kml)),
+            schedule(with_scheduler("lottery", kml))
+        );
+        // Three times the tickets get three ticks for every tick of the others
+        let ticks = schedule(with_scheduler("stride", kml));
+        assert_eq!(ticks, vec![1, 2, 0, 0, 1, 2, 0, 0]);
+    }
+    #[test]
+    fn seed_test() {
+        let schedule = |kernel: &mut Kernel| {
+            (0..16)
+                .map(|_| {
+                    kernel.execute("timer").unwrap();
+                    kernel.state().cpus[0].current.unwrap()
+                })
+                .collect::<Vec<_>>()
+        };
+        let mut kernel = with_scheduler(
+            "random",
+            "event timer { tick preempt }
+            initial { tasks = [0, 1, 2, 3]; current = 0; }
+            kernel { events = [timer] }",
+        );
+        let mut replayed = kernel.clone();
+        replayed.set_seed(kernel.seed());
+        // The same seed makes the same choices
+        assert_eq!(schedule(&mut kernel), schedule(&mut replayed));
+        kernel.set_seed(42);
+        let first = schedule(&mut kernel.clone());
+        assert_eq!(first, schedule(&mut kernel.clone()));
+        kernel.set_seed(43);
+        assert_ne!(first, schedule(&mut kernel));
+    }
+    #[test]
+    fn bad_pick_test() {
+        for name in ["unknown", "unweighted"] {
+            let mut kernel = with_scheduler(
+                name,
+                "event yield { stop sched }
+                initial { tasks = [0, 1, 2]; current = 0; }
+                kernel { events = [yield] }",
+            );
+            assert_eq!(kernel.execute("yield"), Err(Error::BadSchedulerPick));
+            assert_eq!(kernel.state().cpus[0].current, Some(0));
+        }
+    }
+    #[test]
+    fn cpu_policy_test() {
+        let mut kernel = with_scheduler(
+            "pinned",
+            "event spin { stop sched }
+            initial { tasks = [0, 1, 2, 3]; current = [0, 1]; }
+            kernel { cpus = 2 events = [spin] }",
+        );
+        kernel.execute("spin@1").unwrap();
+        kernel.execute("spin").unwrap();
+        let current = kernel.state().cpus.iter().map(|cpu| cpu.current);
+        assert_eq!(current.collect::<Vec<_>>(), vec![Some(2), Some(3)]);
+    }
+    #[test]
+    fn registry_test() {
+        /// Newest ready task first
+        #[derive(Debug, Clone)]
+        struct Newest;
+        impl Scheduler for Newest {
+            fn pick(&mut self, state: &AbstractState, _cpu: CpuId) -> Vec<TaskId> {
+                state.ready_tasks().into_iter().max().into_iter().collect()
+            }
+        }
+        let kml = "event timer { tick stop sched }
+            initial { tasks = [0, 1, 2]; current = 1; }
+            kernel { scheduler = newest events = [timer] }";
+        let mut registry = Registry::default();
+        let model = lex_and_parse_kml(kml).unwrap();
+        assert!(Kernel::from_model(model, None, &registry).is_err());
+        // A factory may reject the configuration
+        registry.register("newest", |_| None);
+        let model = lex_and_parse_kml(kml).unwrap();
+        assert_eq!(
+            Kernel::from_model(model, None, &registry).err(),
+            Some(Error::BadKernelConfig)
+        );
+        registry.register("newest", |_| Some(Box::new(Newest)));
+        let model = lex_and_parse_kml(kml).unwrap();
+        let mut kernel = Kernel::from_model(model, None, &registry).unwrap();
+        kernel.execute("timer").unwrap();
+        assert_eq!(kernel.state().cpus[0].current, Some(2));
+        kernel.execute("timer").unwrap();
+        assert_eq!(kernel.state().cpus[0].current, Some(2));
+    }
+    #[test]
+    fn policy_test() {
+        let kml = "event spawn { newtask(5) }
+            event io { block(io) sched }
+            scheduler newest { pick max(priority) then max(id) among ready preempt }
+            scheduler tied { pick max(priority) }
+            initial { tasks = [0, 1, 2]; current = 0; }
+            kernel { events = [spawn, io] }";
+        let mut kernel = with_scheduler("newest", kml);
+        kernel.execute("spawn").unwrap();
+        assert_eq!(kernel.state().cpus[0].current, Some(3));
+        kernel.execute("io").unwrap();
+        assert_eq!(kernel.state().cpus[0].current, Some(2));
+        // Tasks tied on every key may all run next
+        let mut kernel = with_scheduler("tied", kml);
+        kernel.execute("spawn").unwrap();
+        assert_eq!(kernel.state().cpus[0].current, Some(0));
+        kernel.execute("io").unwrap();
+        assert_eq!(kernel.state().cpus[0].current, Some(3));
+        assert_eq!(kernel.successors("io", 0).len(), 2);
     }
 }
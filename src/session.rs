@@ -0,0 +1,50 @@
+use serde::{Deserialize, Serialize};
+
+/// An interactive session, replaying its events from the same seed makes exactly
+/// the same choices
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Session {
+    pub seed: u64,
+    /// Events in the order they succeeded
+    pub events: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernel::Kernel;
+    use crate::parse::lex_and_parse_kml;
+    use crate::scheduler::Registry;
+
+    fn kernel(kml: &str) -> Kernel {
+        Kernel::from_model(lex_and_parse_kml(kml).unwrap(), None, &Registry::default()).unwrap()
+    }
+
+    #[test]
+    fn replay_test() {
+        let kml = "event timer { tick preempt }
+            event spawn { newtask }
+            initial { tasks = [0, 1]; current = 0; }
+            kernel { scheduler = random seed = 18446744073709551615 events = [timer, spawn] }";
+        let mut recorded = kernel(kml);
+        assert_eq!(recorded.seed(), u64::MAX);
+        recorded.set_seed(7);
+        let mut session = Session {
+            seed: recorded.seed(),
+            events: Vec::new(),
+        };
+        for event in ["spawn", "timer", "timer", "spawn", "timer", "timer"] {
+            recorded.execute(event).unwrap();
+            session.events.push(event.to_string());
+        }
+        // A recorded session replays to the same state from a fresh kernel
+        let json = serde_json::to_string(&session).unwrap();
+        let session: Session = serde_json::from_str(&json).unwrap();
+        let mut replayed = kernel(kml);
+        replayed.set_seed(session.seed);
+        for event in &session.events {
+            replayed.execute(event).unwrap();
+        }
+        assert_eq!(replayed.state(), recorded.state());
+    }
+}
@@ -1,28 +1,767 @@
+use crate::error::Error;
+use crate::parse::{InitialConfig, InitialDef};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
 pub type TaskId = u32;
+/// A process is identified by the id of its first thread
+pub type ProcessId = u32;
+pub type Fd = u32;
+pub type PipeId = u32;
+/// Signal number in `1..NSIG`
+pub type Signal = u32;
+/// Virtual page number
+pub type Page = u32;
+
+pub const NSIG: Signal = 32;
+/// The signal that can be neither blocked, ignored nor handled
+pub const SIGKILL: Signal = 9;
 
-#[derive(Debug, PartialEq, Eq)]
+/// First page of the heap, which grows upwards with `brk`
+pub const HEAP_START: Page = 0x1000;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum TaskStatus {
     Ready,
     Running,
+    Blocked,
+    /// Exited but not yet reaped by its parent
+    Zombie,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct TaskControlBlock {
     pub id: TaskId,
     pub status: TaskStatus,
+    /// Process the task is a thread of
+    pub process: ProcessId,
+    #[serde(default)]
+    pub parent: Option<TaskId>,
+    #[serde(default)]
+    pub children: Vec<TaskId>,
+    /// Static priority, a larger value means a more important task
+    #[serde(default)]
+    pub priority: i32,
+    /// Nice value in `-20..=19`, a smaller value means a larger cpu share
+    #[serde(default)]
+    pub nice: i32,
+    /// Time a sleeping task is woken up at
+    #[serde(default)]
+    pub wake_at: Option<u64>,
+    /// Signals sent but not yet delivered
+    #[serde(default)]
+    pub pending: BTreeSet<Signal>,
+    /// Signals whose delivery is postponed
+    #[serde(default)]
+    pub sigmask: BTreeSet<Signal>,
+    /// Dispositions other than `SigAction::Default`
+    #[serde(default)]
+    pub sigactions: BTreeMap<Signal, SigAction>,
+    /// Last message received
+    #[serde(default)]
+    pub message: Option<Message>,
+    /// Caller blocked until current task replies to it
+    #[serde(default)]
+    pub reply_to: Option<TaskId>,
+    /// Outcome of the last action subject to a resource limit, `None` on success
+    #[serde(default)]
+    pub errno: Option<Errno>,
+    #[serde(default)]
+    pub stats: TaskStats,
+    /// Ticks run since the task was last scheduled, zero unless running
+    #[serde(default)]
+    pub slice_used: u32,
+    /// Queue of the multi-level feedback queue scheduler, 0 is the highest
+    #[serde(default)]
+    pub level: u32,
+    /// Running time weighted by nice value, a tick of a nice 0 task counts `NICE_0_WEIGHT`
+    #[serde(default)]
+    pub vruntime: u64,
+    /// Timing of a real-time task
+    #[serde(default)]
+    pub rt: Option<RealTime>,
+    /// Share of the cpu under proportional-share schedulers
+    #[serde(default = "default_tickets")]
+    pub tickets: u32,
+    /// Virtual time of the stride scheduler, advanced by `stride()` per tick run
+    #[serde(default)]
+    pub pass: u64,
+}
+
+impl TaskControlBlock {
+    /// A ready task without parent, the only thread of its own process
+    pub fn new(id: TaskId) -> Self {
+        Self {
+            id,
+            status: TaskStatus::Ready,
+            process: id,
+            parent: None,
+            children: Vec::new(),
+            priority: 0,
+            nice: 0,
+            wake_at: None,
+            pending: BTreeSet::new(),
+            sigmask: BTreeSet::new(),
+            sigactions: BTreeMap::new(),
+            message: None,
+            reply_to: None,
+            errno: None,
+            stats: TaskStats::default(),
+            slice_used: 0,
+            level: 0,
+            vruntime: 0,
+            rt: None,
+            tickets: DEFAULT_TICKETS,
+            pass: 0,
+        }
+    }
+    pub fn sigaction(&self, sig: Signal) -> SigAction {
+        self.sigactions.get(&sig).copied().unwrap_or_default()
+    }
+    /// Advance of the pass per tick run, inversely proportional to the tickets
+    pub fn stride(&self) -> u64 {
+        STRIDE1 / self.tickets as u64
+    }
+}
+
+/// Tickets of a task unless set otherwise
+pub const DEFAULT_TICKETS: u32 = 100;
+
+/// Stride of a task holding a single ticket
+pub const STRIDE1: u64 = 1 << 20;
+
+fn default_tickets() -> u32 {
+    DEFAULT_TICKETS
+}
+
+/// A real-time task runs a job per release, each job must complete within its
+/// relative deadline
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct RealTime {
+    /// Whether jobs are released by `release` rather than every period
+    pub sporadic: bool,
+    /// Time between releases, the minimum one for a sporadic task
+    pub period: u32,
+    /// Worst-case execution time of a job
+    pub wcet: u32,
+    pub deadline: u32,
+    /// Release time of current job
+    pub release: u64,
+    /// Ticks current job ran
+    pub executed: u32,
+    /// Whether current job completed, the task then waits for its next release
+    pub done: bool,
+    /// Whether current job missed its deadline
+    pub missed: bool,
+    /// Jobs that missed their deadline
+    pub misses: u64,
+    /// Whether current job ran longer than its worst-case execution time
+    #[serde(default)]
+    pub overrun: bool,
+    /// Jobs that ran longer than their worst-case execution time
+    #[serde(default)]
+    pub overruns: u64,
+}
+
+impl RealTime {
+    /// Start the job released at `release`
+    pub fn start_job(&mut self, release: u64) {
+        self.release = release;
+        self.executed = 0;
+        self.done = false;
+        self.missed = false;
+        self.overrun = false;
+    }
+    /// Time current job must complete by
+    pub fn absolute_deadline(&self) -> u64 {
+        self.release + self.deadline as u64
+    }
+}
+
+/// Per-task accounting, as real kernels export it through `/proc`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct TaskStats {
+    /// Times the task was picked by the scheduler
+    pub scheduled: u64,
+    /// Events during which the task was running
+    pub running_events: u64,
+    /// Ticks the task spent ready
+    pub ready_time: u64,
+}
+
+/// Error code returned to a task by an action that exceeded a resource limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Errno {
+    /// Too many tasks
+    Again,
+    /// Too many open descriptors
+    TooManyFiles,
+    /// Too many resident pages
+    NoMemory,
+}
+
+/// What delivering a signal does
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SigAction {
+    /// Terminate the task
+    #[default]
+    Default,
+    /// Discard the signal when it is sent
+    Ignore,
+    /// Run a user handler, which the model does not track further
+    Handle,
 }
 
-#[derive(Debug)]
+/// Resources shared by the threads of a process
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Process {
+    /// Threads that have not exited, the process is torn down once there are none
+    pub threads: Vec<TaskId>,
+    #[serde(default)]
+    pub memory: AddressSpace,
+    /// Open file descriptors
+    #[serde(default)]
+    pub fds: BTreeMap<Fd, File>,
+}
+
+/// What a file descriptor refers to
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum File {
+    Regular(String),
+    PipeRead(PipeId),
+    PipeWrite(PipeId),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Pipe {
+    pub capacity: u32,
+    /// Amount of buffered data
+    pub buffered: u32,
+    /// Number of descriptors referring to each end
+    pub readers: u32,
+    pub writers: u32,
+    /// Tasks blocked in `read` or `write` with the amount they transfer, in wakeup order
+    pub read_waiters: Vec<(TaskId, u32)>,
+    pub write_waiters: Vec<(TaskId, u32)>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Permission {
+    pub read: bool,
+    pub write: bool,
+    pub exec: bool,
+}
+
+impl Permission {
+    /// Whether an access is allowed, where `access` only has the accessed bit set
+    pub fn allows(&self, access: Permission) -> bool {
+        (self.read || !access.read) && (self.write || !access.write) && (self.exec || !access.exec)
+    }
+}
+
+/// A range of mapped pages
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct MemoryArea {
+    pub start: Page,
+    pub pages: u32,
+    pub perm: Permission,
+}
+
+impl MemoryArea {
+    pub fn end(&self) -> Page {
+        self.start + self.pages
+    }
+    pub fn contains(&self, page: Page) -> bool {
+        (self.start..self.end()).contains(&page)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct AddressSpace {
+    /// Areas mapped by `mmap`, sorted by start page and never overlapping
+    pub areas: Vec<MemoryArea>,
+    /// Heap size in pages, the heap is readable and writable
+    pub brk: u32,
+    /// Pages backed by a frame
+    pub resident: BTreeSet<Page>,
+}
+
+impl AddressSpace {
+    pub fn heap(&self) -> MemoryArea {
+        MemoryArea {
+            start: HEAP_START,
+            pages: self.brk,
+            perm: Permission {
+                read: true,
+                write: true,
+                exec: false,
+            },
+        }
+    }
+    /// The area a page is mapped in
+    pub fn area(&self, page: Page) -> Option<MemoryArea> {
+        let heap = self.heap();
+        if heap.contains(page) {
+            return Some(heap);
+        }
+        self.areas.iter().find(|area| area.contains(page)).cloned()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Message {
+    pub sender: TaskId,
+    pub payload: u32,
+}
+
+/// Synchronous IPC endpoint, at most one of the queues is non-empty
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Endpoint {
+    /// Tasks blocked in `send` or `call` with their payload and whether they
+    /// wait for a reply, in wakeup order
+    pub senders: Vec<(TaskId, u32, bool)>,
+    /// Tasks blocked in `recv`, in wakeup order
+    pub receivers: Vec<TaskId>,
+}
+
+/// Physical frames shared by all tasks
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct FramePool {
+    pub total: u32,
+    pub free: u32,
+}
+
+/// Counting semaphore
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Semaphore {
+    pub count: u32,
+    /// Tasks blocked in `sem_down`, in wakeup order
+    pub waiters: Vec<TaskId>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Mutex {
+    pub owner: Option<TaskId>,
+    /// Tasks blocked in `mutex_lock`, in hand-off order
+    pub waiters: Vec<TaskId>,
+}
+
+pub type CpuId = usize;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Cpu {
+    /// Task running on the cpu, `None` when the cpu runs its idle task. The idle task
+    /// is not one of `tasks`: it has no id, is never ready and runs only while no task
+    /// does, so it takes no part in scheduling.
+    pub current: Option<TaskId>,
+    #[serde(default = "enabled")]
+    pub irq_enabled: bool,
+    /// Nesting depth of interrupt handlers
+    #[serde(default)]
+    pub irq_depth: u32,
+    /// Interrupt events deferred while interrupts are disabled
+    #[serde(default)]
+    pub pending_irqs: BTreeSet<String>,
+}
+
+impl Cpu {
+    /// An idle cpu with interrupts enabled
+    pub fn new() -> Self {
+        Self {
+            current: None,
+            irq_enabled: true,
+            irq_depth: 0,
+            pending_irqs: BTreeSet::new(),
+        }
+    }
+}
+
+fn enabled() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct AbstractState {
-    pub current_task: TaskId,
+    /// Logical clock, advanced by `tick`
+    #[serde(default)]
+    pub time: u64,
+    pub cpus: Vec<Cpu>,
     pub tasks: Vec<TaskControlBlock>,
+    pub processes: BTreeMap<ProcessId, Process>,
+    /// Blocked tasks of each named wait queue, in wakeup order.
+    /// Empty queues are removed so that equal states compare equal.
+    #[serde(default)]
+    pub wait_queues: BTreeMap<String, Vec<TaskId>>,
+    /// The task orphans are reparented to
+    #[serde(default)]
+    pub init_task: Option<TaskId>,
+    #[serde(default)]
+    pub semaphores: BTreeMap<String, Semaphore>,
+    /// Locked mutexes, a mutex is removed once it is unlocked with no waiters
+    #[serde(default)]
+    pub mutexes: BTreeMap<String, Mutex>,
+    /// `None` if memory is not modeled
+    #[serde(default)]
+    pub frames: Option<FramePool>,
+    /// Switches from one task to another made by the scheduler
+    #[serde(default)]
+    pub context_switches: u64,
+    /// Pipes with at least one open end
+    #[serde(default)]
+    pub pipes: BTreeMap<PipeId, Pipe>,
+    /// Endpoints with blocked tasks, an endpoint is removed once nobody waits on it
+    #[serde(default)]
+    pub endpoints: BTreeMap<String, Endpoint>,
+    /// Ready tasks in the order they became ready
+    #[serde(default)]
+    pub ready_queue: Vec<TaskId>,
 }
 
 impl AbstractState {
-    pub fn new() -> Self {
+    pub fn new(cpus: usize) -> Self {
         Self {
-            current_task: 0,
+            time: 0,
+            cpus: vec![Cpu::new(); cpus],
             tasks: Vec::new(),
+            processes: BTreeMap::new(),
+            wait_queues: BTreeMap::new(),
+            init_task: None,
+            semaphores: BTreeMap::new(),
+            mutexes: BTreeMap::new(),
+            frames: None,
+            context_switches: 0,
+            pipes: BTreeMap::new(),
+            endpoints: BTreeMap::new(),
+            ready_queue: Vec::new(),
+        }
+    }
+    pub fn task(&self, id: TaskId) -> Option<&TaskControlBlock> {
+        self.tasks.iter().find(|task| task.id == id)
+    }
+    pub fn task_mut(&mut self, id: TaskId) -> Option<&mut TaskControlBlock> {
+        self.tasks.iter_mut().find(|task| task.id == id)
+    }
+    /// Process a task is a thread of, `None` once it is torn down
+    pub fn process_of(&self, id: TaskId) -> Option<&Process> {
+        self.processes.get(&self.task(id)?.process)
+    }
+    pub fn process_of_mut(&mut self, id: TaskId) -> Option<&mut Process> {
+        let process = self.task(id)?.process;
+        self.processes.get_mut(&process)
+    }
+    /// Lowest id that neither a task, a process nor a reference to a task uses. Ids are
+    /// reused like pids, so creating and reaping tasks does not grow the state space.
+    pub fn free_task_id(&self) -> TaskId {
+        let used: BTreeSet<TaskId> = self
+            .tasks
+            .iter()
+            .flat_map(|task| {
+                [Some(task.id), task.parent, task.reply_to]
+                    .into_iter()
+                    .chain([task.message.as_ref().map(|message| message.sender)])
+                    .flatten()
+            })
+            .chain(self.processes.keys().copied())
+            .chain(self.init_task)
+            .collect();
+        (0..).find(|id| !used.contains(id)).unwrap()
+    }
+    /// Whether a task is the zombie of a process that was torn down
+    pub fn exited(&self, id: TaskId) -> bool {
+        self.task(id)
+            .is_some_and(|task| task.status == TaskStatus::Zombie)
+            && !self.processes.contains_key(&id)
+    }
+    /// Tasks waiting in any wait queue, on any synchronization object or for a reply
+    pub fn waiters(&self) -> impl Iterator<Item = TaskId> + '_ {
+        self.wait_queues
+            .values()
+            .flatten()
+            .chain(self.semaphores.values().flat_map(|sem| &sem.waiters))
+            .chain(self.mutexes.values().flat_map(|mutex| &mutex.waiters))
+            .copied()
+            .chain(self.pipes.values().flat_map(|pipe| {
+                pipe.read_waiters
+                    .iter()
+                    .chain(&pipe.write_waiters)
+                    .map(|&(id, _)| id)
+            }))
+            .chain(self.endpoints.values().flat_map(|endpoint| {
+                endpoint
+                    .senders
+                    .iter()
+                    .map(|&(id, _, _)| id)
+                    .chain(endpoint.receivers.iter().copied())
+            }))
+            .chain(self.tasks.iter().filter_map(|task| task.reply_to))
+    }
+    /// Build the state described by an `initial` block.
+    /// The i-th current task runs on cpu i, remaining cpus are idle.
+    pub fn from_def(def: &InitialDef, cpus: usize) -> Self {
+        let mut state = Self::new(cpus);
+        let mut current = &Vec::new();
+        for config in &def.configs {
+            match config {
+                InitialConfig::Tasks(ids) => {
+                    state.tasks = ids.iter().map(|&id| TaskControlBlock::new(id)).collect();
+                    state.processes = ids
+                        .iter()
+                        .map(|&id| {
+                            let threads = vec![id];
+                            (
+                                id,
+                                Process {
+                                    threads,
+                                    ..Default::default()
+                                },
+                            )
+                        })
+                        .collect();
+                }
+                InitialConfig::Current(ids) => current = ids,
+                InitialConfig::Init(id) => state.init_task = Some(*id),
+                InitialConfig::Time(time) => state.time = *time as u64,
+                _ => {}
+            }
+        }
+        // Variables are set once all tasks exist
+        for config in &def.configs {
+            match config {
+                InitialConfig::Priority(id, priority) => {
+                    if let Some(task) = state.task_mut(*id) {
+                        task.priority = *priority;
+                    }
+                }
+                InitialConfig::Nice(id, nice) => {
+                    if let Some(task) = state.task_mut(*id) {
+                        task.nice = *nice;
+                    }
+                }
+                InitialConfig::Blocked(queue, ids) => {
+                    for &id in ids {
+                        if let Some(task) = state.task_mut(id) {
+                            task.status = TaskStatus::Blocked;
+                        }
+                    }
+                    if !ids.is_empty() {
+                        state.wait_queues.insert(queue.0.clone(), ids.clone());
+                    }
+                }
+                InitialConfig::Semaphore(name, count) => {
+                    let sem = Semaphore {
+                        count: *count,
+                        waiters: Vec::new(),
+                    };
+                    state.semaphores.insert(name.0.clone(), sem);
+                }
+                InitialConfig::Mutex(name, owner) => {
+                    let mutex = Mutex {
+                        owner: Some(*owner),
+                        waiters: Vec::new(),
+                    };
+                    state.mutexes.insert(name.0.clone(), mutex);
+                }
+                _ => {}
+            }
+        }
+        for (cpu, &id) in state.cpus.iter_mut().zip(current) {
+            cpu.current = Some(id);
+        }
+        for &id in current {
+            if let Some(task) = state.task_mut(id) {
+                task.status = TaskStatus::Running;
+            }
+        }
+        state.ready_queue = state.ready_tasks();
+        state
+    }
+    /// Queue ready tasks in task order if the state has no ready queue, as states
+    /// written by hand usually do not
+    pub fn queue_ready(&mut self) {
+        if self.ready_queue.is_empty() {
+            self.ready_queue = self.ready_tasks();
+        }
+    }
+    /// The state without its cumulative accounting, which only ever grows and would
+    /// keep apart states that otherwise behave the same
+    pub fn without_stats(&self) -> AbstractState {
+        let mut state = self.clone();
+        state.context_switches = 0;
+        for task in state.tasks.iter_mut() {
+            task.stats = TaskStats::default();
+            if let Some(rt) = task.rt.as_mut() {
+                rt.misses = 0;
+                rt.overruns = 0;
+            }
+        }
+        state
+    }
+    /// Jobs of all tasks that missed their deadline
+    pub fn deadline_misses(&self) -> u64 {
+        self.tasks
+            .iter()
+            .filter_map(|task| task.rt.as_ref())
+            .map(|rt| rt.misses)
+            .sum()
+    }
+    /// Jobs of all tasks that ran longer than their worst-case execution time
+    pub fn wcet_overruns(&self) -> u64 {
+        self.tasks
+            .iter()
+            .filter_map(|task| task.rt.as_ref())
+            .map(|rt| rt.overruns)
+            .sum()
+    }
+    /// Ids of ready tasks in task order
+    pub fn ready_tasks(&self) -> Vec<TaskId> {
+        self.tasks
+            .iter()
+            .filter(|task| task.status == TaskStatus::Ready)
+            .map(|task| task.id)
+            .collect()
+    }
+    /// Check that the state is one the kernel model can reach
+    pub fn validate(&self) -> Result<(), Error> {
+        for (i, task) in self.tasks.iter().enumerate() {
+            if self.tasks[..i].iter().any(|t| t.id == task.id) {
+                return Err(Error::BadInitialState);
+            }
+            // Only tasks on a cpu run, and a task waits in at most one list
+            let on_cpus = self
+                .cpus
+                .iter()
+                .filter(|cpu| cpu.current == Some(task.id))
+                .count();
+            let queued = self.waiters().filter(|&id| id == task.id).count();
+            if (task.status == TaskStatus::Running) != (on_cpus == 1)
+                || on_cpus > 1
+                || queued > 1
+                || (queued == 1 && task.status != TaskStatus::Blocked)
+                || (task.wake_at.is_some() && (queued == 1 || task.status != TaskStatus::Blocked))
+                || (task.status == TaskStatus::Zombie
+                    && task.parent.is_none()
+                    && !self.processes.contains_key(&task.id))
+                || task.sigmask.contains(&SIGKILL)
+                || task.sigactions.contains_key(&SIGKILL)
+                || (task.slice_used > 0 && task.status != TaskStatus::Running)
+                || task.tickets == 0
+                || !(-20..=19).contains(&task.nice)
+            {
+                return Err(Error::BadInitialState);
+            }
+            // Parent and children links must agree
+            let linked = |parent: TaskId, child: TaskId| {
+                self.tasks
+                    .iter()
+                    .any(|t| t.id == parent && t.children.contains(&child))
+            };
+            if task.parent.is_some_and(|parent| !linked(parent, task.id))
+                || task.children.iter().any(|&child| {
+                    self.tasks
+                        .iter()
+                        .all(|t| t.id != child || t.parent != Some(task.id))
+                })
+            {
+                return Err(Error::BadInitialState);
+            }
+            // Threads that have not exited belong to their process
+            let in_process = self
+                .processes
+                .get(&task.process)
+                .is_some_and(|process| process.threads.contains(&task.id));
+            if (task.status != TaskStatus::Zombie) != in_process
+                || (task.id != task.process && task.status == TaskStatus::Zombie)
+            {
+                return Err(Error::BadInitialState);
+            }
+        }
+        for (&id, process) in &self.processes {
+            if self.task(id).is_none_or(|task| task.process != id)
+                || process.threads.is_empty()
+                || process
+                    .threads
+                    .iter()
+                    .any(|&thread| self.task(thread).is_none_or(|task| task.process != id))
+            {
+                return Err(Error::BadInitialState);
+            }
+        }
+        if self
+            .init_task
+            .is_some_and(|init| self.tasks.iter().all(|t| t.id != init))
+        {
+            return Err(Error::BadInitialState);
+        }
+        if self.wait_queues.values().any(|queue| queue.is_empty())
+            || self
+                .endpoints
+                .values()
+                .any(|endpoint| endpoint.senders.is_empty() == endpoint.receivers.is_empty())
+            || self.waiters().any(|id| self.task(id).is_none())
+        {
+            return Err(Error::BadInitialState);
+        }
+        // The ready queue holds every ready task once
+        let mut queued = self.ready_queue.clone();
+        let mut ready = self.ready_tasks();
+        queued.sort();
+        ready.sort();
+        if queued != ready {
+            return Err(Error::BadInitialState);
+        }
+        // A mutex is owned by a task that has not exited
+        for mutex in self.mutexes.values() {
+            let owner = mutex.owner.and_then(|owner| self.task(owner));
+            if owner.is_none_or(|owner| owner.status == TaskStatus::Zombie) {
+                return Err(Error::BadInitialState);
+            }
+        }
+        // Pipe reference counts must match the descriptors
+        for (&id, pipe) in &self.pipes {
+            let count = |end: File| {
+                self.processes
+                    .values()
+                    .flat_map(|process| process.fds.values())
+                    .filter(|&file| *file == end)
+                    .count() as u32
+            };
+            if pipe.readers != count(File::PipeRead(id))
+                || pipe.writers != count(File::PipeWrite(id))
+                || pipe.buffered > pipe.capacity
+            {
+                return Err(Error::BadInitialState);
+            }
+        }
+        // Memory areas never wrap around the address space
+        for process in self.processes.values() {
+            let memory = &process.memory;
+            if HEAP_START.checked_add(memory.brk).is_none()
+                || memory
+                    .areas
+                    .iter()
+                    .any(|area| area.start.checked_add(area.pages).is_none())
+            {
+                return Err(Error::BadInitialState);
+            }
+        }
+        if let Some(frames) = &self.frames {
+            let used = self
+                .processes
+                .values()
+                .map(|process| process.memory.resident.len() as u32)
+                .sum::<u32>();
+            if used + frames.free != frames.total {
+                return Err(Error::BadInitialState);
+            }
+        }
+        if self.cpus.is_empty()
+            || self
+                .cpus
+                .iter()
+                .any(|cpu| cpu.current.is_some_and(|id| self.task(id).is_none()))
+        {
+            return Err(Error::BadInitialState);
         }
+        Ok(())
     }
 }